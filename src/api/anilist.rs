@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::metrics::provider_request;
+
 #[derive(Serialize)]
 struct RequestBody<T: Serialize> {
     query: String,
@@ -23,17 +25,34 @@ pub struct Media {
     pub description: String,
     #[serde(rename = "coverImage")]
     pub cover_image: CoverImage,
+    #[serde(rename = "siteUrl")]
+    pub site_url: String,
 }
 
 #[derive(Deserialize)]
 pub struct Title {
-    pub english: String,
+    pub english: Option<String>,
+    pub romaji: String,
+    pub native: Option<String>,
+}
+
+impl Title {
+    /// Title to show for a language tag: the native title for Japanese, the English title for
+    /// English and the romanization for everything else or when a title is missing.
+    pub fn preferred(&self, language: &str) -> &str {
+        let title = match language.split('-').next() {
+            Some("ja") => self.native.as_deref(),
+            Some("en") => self.english.as_deref(),
+            _ => None,
+        };
+        title.unwrap_or(&self.romaji)
+    }
 }
 
 #[derive(Deserialize)]
 pub struct CoverImage {
     pub large: String,
-    pub color: String,
+    pub color: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -41,45 +60,66 @@ pub struct Vars {
     id: i64,
 }
 
-pub async fn get_data(id: &i64) -> Result<Media, String> {
-    let endpoint = "https://graphql.anilist.co/";
+pub const BASE_URL: &str = "https://graphql.anilist.co";
 
-    let query = r#"
-        query ($id: Int) {
-            Media (id: $id, type: ANIME) {
-                id
-                title {
-                    english
-                }
-                description (asHtml: false)
-                coverImage {
-                    large
-                    color
-                }
-            }
+/// AniList GraphQL client, the API needs no authentication.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+impl Client {
+    /// `base_url` is [`BASE_URL`] outside of tests.
+    pub fn new(base_url: String) -> Client {
+        Client {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
         }
-    "#;
+    }
 
-    let variables = Vars { id: id.clone() };
+    pub async fn get_media(&self, id: &i64) -> Result<Media, String> {
+        let endpoint = format!("{}/", self.base_url);
 
-    let client = reqwest::Client::new();
+        let query = r#"
+            query ($id: Int) {
+                Media (id: $id, type: ANIME) {
+                    id
+                    title {
+                        english
+                        romaji
+                        native
+                    }
+                    description (asHtml: false)
+                    coverImage {
+                        large
+                        color
+                    }
+                    siteUrl
+                }
+            }
+        "#;
 
-    let request_body = RequestBody {
-        query: query.to_string(),
-        variables,
-    };
+        let variables = Vars { id: *id };
 
-    let resp = client
-        .post(endpoint)
-        .json(&request_body)
-        .send()
-        .await
-        .unwrap()
-        .json::<ResponseBody>()
-        .await;
+        let request_body = RequestBody {
+            query: query.to_string(),
+            variables,
+        };
 
-    match resp {
-        Ok(response_body) => Ok(response_body.data.media),
-        Err(e) => Err(e.to_string()),
+        provider_request("anilist", async {
+            let response_body = self
+                .http
+                .post(endpoint)
+                .json(&request_body)
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .json::<ResponseBody>()
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(response_body.data.media)
+        })
+        .await
     }
 }
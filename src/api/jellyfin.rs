@@ -0,0 +1,68 @@
+use serde::Deserialize;
+
+use crate::metrics::provider_request;
+
+#[derive(Debug, Deserialize)]
+struct ItemsResponse {
+    #[serde(rename = "Items")]
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Item {
+    #[serde(rename = "ProviderIds", default)]
+    pub provider_ids: ProviderIds,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ProviderIds {
+    #[serde(rename = "Tmdb")]
+    pub tmdb: Option<String>,
+}
+
+/// Jellyfin API client, authenticated with an API key.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    url: String,
+    api_key: String,
+}
+
+impl Client {
+    pub fn new(url: String, api_key: String) -> Client {
+        Client {
+            http: reqwest::Client::new(),
+            url,
+            api_key,
+        }
+    }
+
+    /// Returns the TMDB ids of all movies in the Jellyfin library.
+    pub async fn get_movie_tmdb_ids(&self) -> Result<Vec<i64>, String> {
+        let endpoint = format!("{}/Items", self.url.trim_end_matches('/'));
+
+        let items = provider_request("jellyfin", async {
+            self.http
+                .get(endpoint)
+                .header("X-Emby-Token", &self.api_key)
+                .query(&[
+                    ("Recursive", "true"),
+                    ("IncludeItemTypes", "Movie"),
+                    ("Fields", "ProviderIds"),
+                ])
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .json::<ItemsResponse>()
+                .await
+                .map_err(|e| e.to_string())
+        })
+        .await?;
+
+        Ok(items
+            .items
+            .into_iter()
+            .filter_map(|item| item.provider_ids.tmdb?.parse().ok())
+            .collect())
+    }
+}
@@ -1,2 +1,3 @@
 pub mod anilist;
+pub mod jellyfin;
 pub mod tmdb;
@@ -1,163 +1,270 @@
-use std::env;
-
 use reqwest::header::AUTHORIZATION;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
-pub struct Configuration {
-    pub images: ImagesConfiguration,
-}
+use crate::metrics::provider_request;
 
-#[derive(Debug, Deserialize)]
-pub struct ImagesConfiguration {
-    pub secure_base_url: String,
-}
+/// Language used when TMDB has no translation in the configured language.
+pub const FALLBACK_LANGUAGE: &str = "en-US";
 
-pub async fn get_configuration() -> Result<Configuration, String> {
-    let endpoint = "https://api.themoviedb.org/3/configuration";
+pub const BASE_URL: &str = "https://api.themoviedb.org/3";
 
-    let tmdb_token =
-        env::var("TMDB_TOKEN").expect("Expected JELLYFIN_ANNOUNCEMENTS_CHANNEL_ID in environment");
+/// TMDB API client, authenticated with a read access token.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: String,
+}
 
-    let client = reqwest::Client::new();
+impl Client {
+    /// `base_url` is [`BASE_URL`] outside of tests.
+    pub fn new(base_url: String, token: String) -> Client {
+        Client {
+            http: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
 
-    let resp = client
-        .get(endpoint)
-        .header(AUTHORIZATION, format!("Bearer {}", tmdb_token))
-        .send()
+    async fn get<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, String> {
+        provider_request("tmdb", async {
+            self.http
+                .get(endpoint)
+                .header(AUTHORIZATION, format!("Bearer {}", self.token))
+                .query(query)
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status())
+                .map_err(|e| e.to_string())?
+                .json::<T>()
+                .await
+                .map_err(|e| e.to_string())
+        })
         .await
-        .unwrap()
-        .json::<Configuration>()
-        .await;
+    }
 
-    match resp {
-        Ok(config) => Ok(config),
-        Err(e) => Err(e.to_string()),
+    /// Fetches an item in `language` together with all of its translations.
+    async fn get_translated<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        language: &str,
+    ) -> Result<T, String> {
+        self.get(
+            endpoint,
+            &[
+                ("language", language),
+                ("append_to_response", "translations"),
+            ],
+        )
+        .await
     }
-}
 
-#[derive(Debug, Deserialize)]
-pub struct Movie {
-    pub title: String,
-    pub overview: String,
-    pub poster_path: String,
-}
+    pub async fn get_configuration(&self) -> Result<Configuration, String> {
+        self.get(&format!("{}/configuration", self.base_url), &[])
+            .await
+    }
 
-pub async fn get_movie(id: &i64) -> Result<Movie, String> {
-    let endpoint = format!("https://api.themoviedb.org/3/movie/{id}");
+    pub async fn get_movie(&self, id: &i64, language: &str) -> Result<Movie, String> {
+        let endpoint = format!("{}/movie/{id}", self.base_url);
 
-    let tmdb_token =
-        env::var("TMDB_TOKEN").expect("Expected JELLYFIN_ANNOUNCEMENTS_CHANNEL_ID in environment");
+        let mut movie: Movie = self.get_translated(&endpoint, language).await?;
+        if movie.overview.is_empty() {
+            movie.overview = movie.translations.fallback_overview();
+        }
+        Ok(movie)
+    }
 
-    let client = reqwest::Client::new();
+    pub async fn get_tv_show(&self, id: &i64, language: &str) -> Result<TvShow, String> {
+        let endpoint = format!("{}/tv/{id}", self.base_url);
 
-    let resp = client
-        .get(endpoint)
-        .header(AUTHORIZATION, format!("Bearer {}", tmdb_token))
-        .send()
-        .await
-        .unwrap()
-        .json::<Movie>()
-        .await;
+        let mut tv_show: TvShow = self.get_translated(&endpoint, language).await?;
+        if tv_show.overview.is_empty() {
+            tv_show.overview = tv_show.translations.fallback_overview();
+        }
+        Ok(tv_show)
+    }
+
+    pub async fn get_season(
+        &self,
+        id: &i64,
+        season_number: &i64,
+        language: &str,
+    ) -> Result<Season, String> {
+        let endpoint = format!("{}/tv/{id}/season/{season_number}", self.base_url);
+
+        let mut season: Season = self.get_translated(&endpoint, language).await?;
+        if season.overview.is_empty() {
+            season.overview = season.translations.fallback_overview();
+        }
+        Ok(season)
+    }
 
-    match resp {
-        Ok(movie) => Ok(movie),
-        Err(e) => Err(e.to_string()),
+    pub async fn get_episode(
+        &self,
+        id: &i64,
+        season_number: &i64,
+        episode_number: &i64,
+        language: &str,
+    ) -> Result<Episode, String> {
+        let endpoint = format!(
+            "{}/tv/{id}/season/{season_number}/episode/{episode_number}",
+            self.base_url
+        );
+
+        let mut episode: Episode = self.get_translated(&endpoint, language).await?;
+        if episode.overview.is_empty() {
+            episode.overview = episode.translations.fallback_overview();
+        }
+        Ok(episode)
     }
+
+    pub async fn get_collection(&self, id: &i64, language: &str) -> Result<Collection, String> {
+        let endpoint = format!("{}/collection/{id}", self.base_url);
+
+        // Collections don't support append_to_response, their translations are a separate request
+        let mut collection: Collection = self.get(&endpoint, &[("language", language)]).await?;
+        collection.translations = self.get(&format!("{endpoint}/translations"), &[]).await?;
+        if collection.overview.is_empty() {
+            collection.overview = collection.translations.fallback_overview();
+        }
+        Ok(collection)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Translations {
+    pub translations: Vec<Translation>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct TvShow {
-    pub name: String,
+pub struct Translation {
+    pub iso_639_1: String,
+    pub iso_3166_1: String,
+    pub data: TranslationData,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TranslationData {
+    /// Movies and collections have a title, shows, seasons and episodes a name
+    #[serde(default, alias = "name")]
+    pub title: String,
+    #[serde(default)]
     pub overview: String,
-    pub poster_path: String,
 }
 
-pub async fn get_tv_show(id: &i64) -> Result<TvShow, String> {
-    let endpoint = format!("https://api.themoviedb.org/3/tv/{id}");
+impl Translations {
+    /// Looks up a translation by language tag like `nl-BE`, falling back to any region of the
+    /// same language.
+    pub fn get(&self, language: &str) -> Option<&TranslationData> {
+        let (lang, region) = language.split_once('-').unwrap_or((language, ""));
+        self.translations
+            .iter()
+            .find(|translation| translation.iso_639_1 == lang && translation.iso_3166_1 == region)
+            .or_else(|| {
+                self.translations
+                    .iter()
+                    .find(|translation| translation.iso_639_1 == lang)
+            })
+            .map(|translation| &translation.data)
+    }
+
+    /// Overview to use when the translated one is empty.
+    fn fallback_overview(&self) -> String {
+        self.get(FALLBACK_LANGUAGE)
+            .map(|data| data.overview.clone())
+            .unwrap_or_default()
+    }
+}
 
-    let tmdb_token =
-        env::var("TMDB_TOKEN").expect("Expected JELLYFIN_ANNOUNCEMENTS_CHANNEL_ID in environment");
+#[derive(Debug, Deserialize)]
+pub struct Configuration {
+    pub images: ImagesConfiguration,
+}
 
-    let client = reqwest::Client::new();
+#[derive(Debug, Deserialize)]
+pub struct ImagesConfiguration {
+    pub secure_base_url: String,
+}
 
-    let resp = client
-        .get(endpoint)
-        .header(AUTHORIZATION, format!("Bearer {}", tmdb_token))
-        .send()
-        .await
-        .unwrap()
-        .json::<TvShow>()
-        .await;
+#[derive(Debug, Deserialize)]
+pub struct Movie {
+    pub id: i64,
+    pub title: String,
+    pub overview: String,
+    pub poster_path: Option<String>,
+    #[serde(default)]
+    pub translations: Translations,
+}
 
-    match resp {
-        Ok(tv_show) => Ok(tv_show),
-        Err(e) => Err(e.to_string()),
-    }
+#[derive(Debug, Deserialize)]
+pub struct TvShow {
+    pub id: i64,
+    pub name: String,
+    pub overview: String,
+    pub poster_path: Option<String>,
+    #[serde(default)]
+    pub translations: Translations,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Season {
     pub name: String,
+    pub season_number: i64,
     pub overview: String,
-    pub poster_path: String,
+    pub poster_path: Option<String>,
+    #[serde(default)]
+    pub episodes: Vec<SeasonEpisode>,
+    #[serde(default)]
+    pub translations: Translations,
 }
 
-pub async fn get_season(id: &i64, season_number: &i64) -> Result<Season, String> {
-    let endpoint = format!("https://api.themoviedb.org/3/tv/{id}/season/{season_number}");
-
-    let tmdb_token =
-        env::var("TMDB_TOKEN").expect("Expected JELLYFIN_ANNOUNCEMENTS_CHANNEL_ID in environment");
-
-    let client = reqwest::Client::new();
-
-    let resp = client
-        .get(endpoint)
-        .header(AUTHORIZATION, format!("Bearer {}", tmdb_token))
-        .send()
-        .await
-        .unwrap()
-        .json::<Season>()
-        .await;
-
-    match resp {
-        Ok(season) => Ok(season),
-        Err(e) => Err(e.to_string()),
-    }
+#[derive(Debug, Deserialize)]
+pub struct SeasonEpisode {
+    pub episode_number: i32,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Episode {
+    #[serde(default)]
+    pub name: String,
     pub overview: String,
-    pub still_path: String,
+    pub still_path: Option<String>,
     pub episode_number: i32,
+    /// `standard`, `mid_season` or `finale`
+    #[serde(default)]
+    pub episode_type: String,
+    #[serde(default)]
+    pub translations: Translations,
 }
 
-pub async fn get_episode(
-    id: &i64,
-    season_number: &i64,
-    episode_number: &i64,
-) -> Result<Episode, String> {
-    let endpoint = format!(
-        "https://api.themoviedb.org/3/tv/{id}/season/{season_number}/episode/{episode_number}"
-    );
-
-    let tmdb_token =
-        env::var("TMDB_TOKEN").expect("Expected JELLYFIN_ANNOUNCEMENTS_CHANNEL_ID in environment");
-
-    let client = reqwest::Client::new();
+#[derive(Debug, Deserialize)]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+    pub overview: String,
+    pub poster_path: Option<String>,
+    pub backdrop_path: Option<String>,
+    pub parts: Vec<CollectionPart>,
+    #[serde(default)]
+    pub translations: Translations,
+}
 
-    let resp = client
-        .get(endpoint)
-        .header(AUTHORIZATION, format!("Bearer {}", tmdb_token))
-        .send()
-        .await
-        .unwrap()
-        .json::<Episode>()
-        .await;
+#[derive(Debug, Deserialize)]
+pub struct CollectionPart {
+    pub id: i64,
+    pub title: String,
+    /// `null` or empty for parts that were not released yet
+    #[serde(default)]
+    pub release_date: Option<String>,
+}
 
-    match resp {
-        Ok(episode) => Ok(episode),
-        Err(e) => Err(e.to_string()),
+impl CollectionPart {
+    pub fn year(&self) -> Option<&str> {
+        self.release_date.as_deref()?.get(0..4)
     }
 }
@@ -0,0 +1,141 @@
+use serenity::builder::{CreateEmbed, CreateMessage};
+use serenity::json::{self, Value};
+use serenity::model::mention::Mention;
+use serenity::model::prelude::{GuildId, UserId};
+use serenity::model::Timestamp;
+use tracing::{error, warn};
+
+use crate::db::Database;
+use crate::discord::DiscordOps;
+use crate::utils::color::Rgb;
+use crate::utils::markdown::{truncate, DESCRIPTION_LIMIT, TITLE_LIMIT};
+
+/// Discord allows at most this many embeds in a single message.
+const EMBEDS_PER_MESSAGE: usize = 10;
+/// Discord rejects a message whose embeds hold more characters than this together.
+const CHARACTERS_PER_MESSAGE: usize = 6000;
+
+const SUCCESS_COLOR: Rgb = (87, 242, 135);
+const FAILURE_COLOR: Rgb = (237, 66, 69);
+
+/// How an action turned out.
+pub enum Outcome {
+    Success(String),
+    Failure(String),
+}
+
+/// A compact embed describing `action`, done by or on behalf of `user_id`.
+pub fn entry(user_id: UserId, action: &str, outcome: Outcome) -> CreateEmbed {
+    let (text, color) = match outcome {
+        Outcome::Success(text) => (text, SUCCESS_COLOR),
+        Outcome::Failure(text) => (text, FAILURE_COLOR),
+    };
+    let description = format!("{}\n{text}", Mention::from(user_id));
+
+    CreateEmbed::new()
+        .title(truncate(action, TITLE_LIMIT, None))
+        .description(truncate(&description, DESCRIPTION_LIMIT, None))
+        .color(color)
+        .timestamp(Timestamp::now())
+}
+
+/// Posts `entries` in the audit channel of the guild, if it has one.
+pub async fn log(
+    discord: &dyn DiscordOps,
+    db: &Database,
+    guild_id: GuildId,
+    entries: Vec<CreateEmbed>,
+) {
+    if entries.is_empty() {
+        return;
+    }
+    let channel_id = match db.guild_settings(guild_id) {
+        Ok(settings) => match settings.audit_channel_id {
+            Some(channel_id) => channel_id,
+            None => return,
+        },
+        Err(e) => {
+            error!(error = %e, "Cannot read guild settings for the audit log");
+            return;
+        }
+    };
+
+    for embeds in batches(entries) {
+        let message = CreateMessage::new().embeds(embeds);
+        if let Err(e) = discord.send_message(channel_id, message).await {
+            warn!(error = %e, "Cannot post to the audit log");
+            return;
+        }
+    }
+}
+
+/// Splits `entries` into messages Discord accepts, keeping their order.
+fn batches(entries: Vec<CreateEmbed>) -> Vec<Vec<CreateEmbed>> {
+    let mut batches: Vec<Vec<CreateEmbed>> = Vec::new();
+    let mut characters = 0;
+    for embed in entries {
+        let length = length(&embed);
+        match batches.last_mut() {
+            Some(batch)
+                if batch.len() < EMBEDS_PER_MESSAGE
+                    && characters + length <= CHARACTERS_PER_MESSAGE =>
+            {
+                characters += length;
+                batch.push(embed);
+            }
+            _ => {
+                characters = length;
+                batches.push(vec![embed]);
+            }
+        }
+    }
+    batches
+}
+
+/// The characters of an embed that count towards the limit of a message.
+fn length(embed: &CreateEmbed) -> usize {
+    let Ok(embed) = json::to_value(embed) else {
+        return 0;
+    };
+    let text = |value: &Value| value.as_str().map_or(0, |text| text.chars().count());
+    let fields = embed["fields"].as_array().map_or(0, |fields| {
+        fields
+            .iter()
+            .map(|field| text(&field["name"]) + text(&field["value"]))
+            .sum()
+    });
+    text(&embed["title"])
+        + text(&embed["description"])
+        + text(&embed["footer"]["text"])
+        + text(&embed["author"]["name"])
+        + fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(count: usize, text: &str) -> Vec<CreateEmbed> {
+        (0..count)
+            .map(|_| entry(UserId::new(1), "Action", Outcome::Success(text.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn batches_at_most_ten_embeds() {
+        let sizes: Vec<usize> = batches(entries(23, "Done")).iter().map(Vec::len).collect();
+
+        assert_eq!(sizes, vec![10, 10, 3]);
+    }
+
+    #[test]
+    fn batches_within_the_character_limit() {
+        let batches = batches(entries(5, &"é".repeat(2500)));
+
+        let sizes: Vec<usize> = batches.iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![2, 2, 1]);
+        for batch in &batches {
+            assert!(batch.iter().map(length).sum::<usize>() <= CHARACTERS_PER_MESSAGE);
+        }
+    }
+}
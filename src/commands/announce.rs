@@ -1,56 +1,84 @@
-use regex::Regex;
-use serenity::builder::{
-    CreateCommand, CreateCommandOption, CreateEmbed, CreateEmbedFooter, CreateMessage,
-};
+use chrono::Utc;
+use serenity::async_trait;
+use serenity::builder::{CreateCommand, CreateEmbed, CreateEmbedFooter, CreateMessage};
 use serenity::model::application::{
     CommandInteraction, CommandOptionType, ResolvedOption, ResolvedValue,
 };
 use serenity::model::mention::Mention;
+use serenity::model::permissions::Permissions;
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
 use serenity::prelude::Context;
-use serenity::utils::MessageBuilder;
+use tracing::error;
 
-use crate::api::anilist::{self, Media};
+use super::SlashCommand;
+use crate::api::anilist::Media;
 use crate::api::tmdb::{
-    get_configuration, get_episode, get_movie, get_season, get_tv_show, Configuration, Episode,
-    Movie, Season, TvShow,
+    Collection, CollectionPart, Configuration, Episode, Movie, Season, Translations, TvShow,
+};
+use crate::audit::{self, Outcome};
+use crate::db::{Database, MediaKind};
+use crate::discord::{DiscordOps, SerenityDiscord};
+use crate::i18n::{self, tr, tr_args};
+use crate::metrics::METRICS;
+use crate::utils::color::{parse_color, Provider, Rgb};
+use crate::utils::markdown::{
+    sanitize, truncate, DESCRIPTION_LIMIT, FIELD_NAME_LIMIT, FIELD_VALUE_LIMIT, TITLE_LIMIT,
 };
-use crate::utils::decode_hex;
 use crate::Handler;
 
-pub async fn run(command: &CommandInteraction, handler: &Handler, ctx: &Context) -> String {
+/// Where an announcement goes and in which language to reply about it.
+struct Target<'a> {
+    locale: &'a str,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    /// Who asked for the announcement, for the audit log
+    user_id: UserId,
+}
+
+async fn run(
+    command: &CommandInteraction,
+    handler: &Handler,
+    ctx: &Context,
+) -> Result<String, String> {
     let options: &[ResolvedOption] = &command.data.options();
+    let locale = command.locale.as_str();
 
-    if command.user.id != handler.admin_user_id {
-        return format!(
-            "Only {} is allowed to run this command!",
-            Mention::from(handler.admin_user_id)
-        );
-    }
+    let settings = super::guild_settings(command, handler)?;
+    let Some(announcements_channel_id) = settings.announcements_channel_id else {
+        return Err(tr(locale, "common.not_configured").to_string());
+    };
+    let target = Target {
+        locale,
+        guild_id: settings.guild_id,
+        channel_id: announcements_channel_id,
+        user_id: command.user.id,
+    };
 
-    let subcommand_name = options.get(0).expect("Expected subcommand").name;
+    let discord = SerenityDiscord::new(ctx);
+
+    let subcommand_name = options.first().expect("Expected subcommand").name;
 
     match &options.first().unwrap().value {
         ResolvedValue::SubCommand(options) => match subcommand_name {
             "anilist" => {
                 let id_option = options.first().unwrap();
                 if let ResolvedValue::Integer(id) = id_option.value {
-                    let api_response = anilist::get_data(&id).await;
+                    let api_response = handler.anilist.get_media(&id).await;
                     match api_response {
-                        Ok(media) => send_anilist_announcement(handler, ctx, media).await,
-                        Err(e) => e,
+                        Ok(media) => {
+                            send_anilist_announcement(handler, &discord, &target, media).await
+                        }
+                        Err(e) => Err(e),
                     }
                 } else {
-                    "Id must be a integer".to_string()
+                    Err(tr(locale, "common.invalid_id").to_string())
                 }
             }
-            _ => "Invalid subcommand".to_string(),
+            _ => Err(tr(locale, "common.invalid_subcommand").to_string()),
         },
         ResolvedValue::SubCommandGroup(options) => match subcommand_name {
             "tmdb" => {
-                let config = match get_configuration().await {
-                    Ok(config) => config,
-                    Err(e) => return e,
-                };
+                let config = handler.tmdb.get_configuration().await?;
                 let subcommand = options.first().unwrap();
                 if let ResolvedValue::SubCommand(options) = &subcommand.value {
                     match subcommand.name {
@@ -60,16 +88,16 @@ pub async fn run(command: &CommandInteraction, handler: &Handler, ctx: &Context)
                             {
                                 id
                             } else {
-                                return "Please provide a valid ID".to_string();
+                                return Err(tr(locale, "common.invalid_id").to_string());
                             };
-                            match get_movie(&id).await {
+                            match handler.tmdb.get_movie(&id, &handler.language).await {
                                 Ok(movie) => {
                                     return send_tmdb_movie_announcement(
-                                        handler, ctx, config, movie,
+                                        handler, &discord, &target, config, movie,
                                     )
                                     .await
                                 }
-                                Err(e) => return e,
+                                Err(e) => Err(e),
                             }
                         }
                         "tv_show" => {
@@ -78,16 +106,16 @@ pub async fn run(command: &CommandInteraction, handler: &Handler, ctx: &Context)
                             {
                                 id
                             } else {
-                                return "Please provide a valid ID".to_string();
+                                return Err(tr(locale, "common.invalid_id").to_string());
                             };
-                            match get_tv_show(&id).await {
+                            match handler.tmdb.get_tv_show(&id, &handler.language).await {
                                 Ok(tv_show) => {
                                     return send_tmdb_show_announcement(
-                                        handler, ctx, config, tv_show,
+                                        handler, &discord, &target, config, tv_show,
                                     )
                                     .await
                                 }
-                                Err(e) => return e,
+                                Err(e) => Err(e),
                             }
                         }
                         "season" => {
@@ -96,26 +124,40 @@ pub async fn run(command: &CommandInteraction, handler: &Handler, ctx: &Context)
                             {
                                 id
                             } else {
-                                return "Please provide a valid ID".to_string();
+                                return Err(tr(locale, "common.invalid_id").to_string());
                             };
                             let season_number = if let ResolvedValue::Integer(season_number) =
                                 options.get(1).unwrap().value
                             {
                                 season_number
                             } else {
-                                return "Please provide a valid number".to_string();
+                                return Err(tr(locale, "common.invalid_number").to_string());
                             };
-                            match get_tv_show(&id).await {
-                                Ok(tv_show) => match get_season(&id, &season_number).await {
-                                    Ok(season) => {
-                                        return send_tmdb_season_announcement(
-                                            handler, ctx, config, tv_show, season,
-                                        )
+                            let spoiler_safe = bool_option(options, "spoiler_safe")
+                                .unwrap_or(handler.spoiler_safe_default);
+                            match handler.tmdb.get_tv_show(&id, &handler.language).await {
+                                Ok(tv_show) => {
+                                    match handler
+                                        .tmdb
+                                        .get_season(&id, &season_number, &handler.language)
                                         .await
+                                    {
+                                        Ok(season) => {
+                                            return send_tmdb_season_announcement(
+                                                handler,
+                                                &discord,
+                                                &target,
+                                                config,
+                                                tv_show,
+                                                season,
+                                                spoiler_safe,
+                                            )
+                                            .await
+                                        }
+                                        Err(e) => Err(e),
                                     }
-                                    Err(e) => return e,
-                                },
-                                Err(e) => return e,
+                                }
+                                Err(e) => Err(e),
                             }
                         }
                         "episode" => {
@@ -124,290 +166,709 @@ pub async fn run(command: &CommandInteraction, handler: &Handler, ctx: &Context)
                             {
                                 id
                             } else {
-                                return "Please provide a valid ID".to_string();
+                                return Err(tr(locale, "common.invalid_id").to_string());
                             };
                             let season_number = if let ResolvedValue::Integer(season_number) =
                                 options.get(1).unwrap().value
                             {
                                 season_number
                             } else {
-                                return "Please provide a valid number".to_string();
+                                return Err(tr(locale, "common.invalid_number").to_string());
                             };
                             let episode_number = if let ResolvedValue::Integer(episode_number) =
                                 options.get(2).unwrap().value
                             {
                                 episode_number
                             } else {
-                                return "Please provide a valid number".to_string();
+                                return Err(tr(locale, "common.invalid_number").to_string());
                             };
-                            match get_tv_show(&id).await {
-                                Ok(tv_show) => match get_season(&id, &season_number).await {
-                                    Ok(season) => {
-                                        match get_episode(&id, &season_number, &episode_number)
-                                            .await
-                                        {
-                                            Ok(episode) => {
-                                                return send_tmdb_episode_announcement(
-                                                    handler, ctx, config, tv_show, season, episode,
+                            let spoiler_safe = bool_option(options, "spoiler_safe")
+                                .unwrap_or(handler.spoiler_safe_default);
+                            match handler.tmdb.get_tv_show(&id, &handler.language).await {
+                                Ok(tv_show) => {
+                                    match handler
+                                        .tmdb
+                                        .get_season(&id, &season_number, &handler.language)
+                                        .await
+                                    {
+                                        Ok(season) => {
+                                            match handler
+                                                .tmdb
+                                                .get_episode(
+                                                    &id,
+                                                    &season_number,
+                                                    &episode_number,
+                                                    &handler.language,
                                                 )
                                                 .await
+                                            {
+                                                Ok(episode) => {
+                                                    return send_tmdb_episode_announcement(
+                                                        handler,
+                                                        &discord,
+                                                        &target,
+                                                        config,
+                                                        tv_show,
+                                                        season,
+                                                        episode,
+                                                        spoiler_safe,
+                                                    )
+                                                    .await
+                                                }
+                                                Err(e) => Err(e),
                                             }
-                                            Err(e) => return e,
                                         }
+                                        Err(e) => Err(e),
                                     }
-                                    Err(e) => return e,
-                                },
-                                Err(e) => return e,
+                                }
+                                Err(e) => Err(e),
                             }
                         }
-                        _ => "Unknown type".to_string(),
+                        "collection" => {
+                            let id = if let ResolvedValue::Integer(id) =
+                                options.first().unwrap().value
+                            {
+                                id
+                            } else {
+                                return Err(tr(locale, "common.invalid_id").to_string());
+                            };
+                            let in_library = bool_option(options, "in_library").unwrap_or(false);
+                            let mut collection =
+                                handler.tmdb.get_collection(&id, &handler.language).await?;
+                            if in_library {
+                                collection = only_in_library(handler, collection, locale).await?;
+                            }
+                            send_tmdb_collection_announcement(
+                                handler, &discord, &target, config, collection,
+                            )
+                            .await
+                        }
+                        _ => Err(tr(locale, "announce.unknown_type").to_string()),
                     }
                 } else {
-                    "No type defined".to_string()
+                    Err(tr(locale, "announce.no_type").to_string())
                 }
             }
-            _ => "Invalid subcommand".to_string(),
+            _ => Err(tr(locale, "common.invalid_subcommand").to_string()),
         },
-        _ => "Invallid command".to_string(),
+        _ => Err(tr(locale, "common.invalid_command").to_string()),
     }
 }
 
-pub fn register() -> CreateCommand {
-    let anilist_subcommand_id_option = CreateCommandOption::new(
+fn register() -> CreateCommand {
+    let anilist_subcommand_id_option =
+        i18n::option(CommandOptionType::Integer, "id", "announce.anilist.id").required(true);
+    let anilist_subcommand =
+        i18n::option(CommandOptionType::SubCommand, "anilist", "announce.anilist")
+            .add_sub_option(anilist_subcommand_id_option);
+    let tmdb_subcommand_group_id_option =
+        i18n::option(CommandOptionType::Integer, "id", "announce.tmdb.id").required(true);
+    let tmdb_subcommand_group_season_number_option = i18n::option(
         CommandOptionType::Integer,
-        "id",
-        "Id of the item on AniList",
+        "season_number",
+        "announce.tmdb.season_number",
     )
     .required(true);
-    let anilist_subcommand = CreateCommandOption::new(
-        CommandOptionType::SubCommand,
-        "anilist",
-        "Announce a new movie, serie or season on Jellyfin",
-    )
-    .add_sub_option(anilist_subcommand_id_option);
-    let tmdb_subcommand_group_id_option =
-        CreateCommandOption::new(CommandOptionType::Integer, "id", "Id of the item on TMDB")
-            .required(true);
-    let tmdb_subcommand_group_season_number_option =
-        CreateCommandOption::new(CommandOptionType::Integer, "season_number", "Season number")
-            .required(true);
-    let tmdb_subcommand_group_episode_number_option = CreateCommandOption::new(
+    let tmdb_subcommand_group_episode_number_option = i18n::option(
         CommandOptionType::Integer,
         "episode_number",
-        "Episode number",
+        "announce.tmdb.episode_number",
     )
     .required(true);
-    let tmdb_subcommand_group_movie_subcommand = CreateCommandOption::new(
+    let tmdb_subcommand_group_spoiler_safe_option = i18n::option(
+        CommandOptionType::Boolean,
+        "spoiler_safe",
+        "announce.tmdb.spoiler_safe",
+    );
+    let tmdb_subcommand_group_movie_subcommand = i18n::option(
         CommandOptionType::SubCommand,
         "movie",
-        "Announce a new movie on Jellyfin",
+        "announce.tmdb.movie",
     )
     .add_sub_option(tmdb_subcommand_group_id_option.clone());
-    let tmdb_subcommand_group_tv_show_subcommand = CreateCommandOption::new(
+    let tmdb_subcommand_group_tv_show_subcommand = i18n::option(
         CommandOptionType::SubCommand,
         "tv_show",
-        "Announce a new TV show on Jellyfin",
+        "announce.tmdb.tv_show",
     )
     .add_sub_option(tmdb_subcommand_group_id_option.clone());
-    let tmdb_subcommand_group_season_subcommand = CreateCommandOption::new(
+    let tmdb_subcommand_group_season_subcommand = i18n::option(
         CommandOptionType::SubCommand,
         "season",
-        "Announce a new season on Jellyfin",
+        "announce.tmdb.season",
     )
     .add_sub_option(tmdb_subcommand_group_id_option.clone())
-    .add_sub_option(tmdb_subcommand_group_season_number_option.clone());
-    let tmdb_subcommand_group_episode_subcommand = CreateCommandOption::new(
+    .add_sub_option(tmdb_subcommand_group_season_number_option.clone())
+    .add_sub_option(tmdb_subcommand_group_spoiler_safe_option.clone());
+    let tmdb_subcommand_group_episode_subcommand = i18n::option(
         CommandOptionType::SubCommand,
         "episode",
-        "Announce a new episode on Jellyfin",
+        "announce.tmdb.episode",
     )
-    .add_sub_option(tmdb_subcommand_group_id_option)
+    .add_sub_option(tmdb_subcommand_group_id_option.clone())
     .add_sub_option(tmdb_subcommand_group_season_number_option)
-    .add_sub_option(tmdb_subcommand_group_episode_number_option);
-    let tmdb_subcommand_group = CreateCommandOption::new(
-        CommandOptionType::SubCommandGroup,
-        "tmdb",
-        "Announce a new movie, series, season or episode on Jellyfin",
+    .add_sub_option(tmdb_subcommand_group_episode_number_option)
+    .add_sub_option(tmdb_subcommand_group_spoiler_safe_option);
+    let tmdb_subcommand_group_in_library_option = i18n::option(
+        CommandOptionType::Boolean,
+        "in_library",
+        "announce.tmdb.in_library",
+    );
+    let tmdb_subcommand_group_collection_subcommand = i18n::option(
+        CommandOptionType::SubCommand,
+        "collection",
+        "announce.tmdb.collection",
     )
-    .add_sub_option(tmdb_subcommand_group_movie_subcommand)
-    .add_sub_option(tmdb_subcommand_group_tv_show_subcommand)
-    .add_sub_option(tmdb_subcommand_group_season_subcommand)
-    .add_sub_option(tmdb_subcommand_group_episode_subcommand);
-    CreateCommand::new("announce")
-        .description("Post an announcement")
+    .add_sub_option(tmdb_subcommand_group_id_option)
+    .add_sub_option(tmdb_subcommand_group_in_library_option);
+    let tmdb_subcommand_group =
+        i18n::option(CommandOptionType::SubCommandGroup, "tmdb", "announce.tmdb")
+            .add_sub_option(tmdb_subcommand_group_movie_subcommand)
+            .add_sub_option(tmdb_subcommand_group_tv_show_subcommand)
+            .add_sub_option(tmdb_subcommand_group_season_subcommand)
+            .add_sub_option(tmdb_subcommand_group_episode_subcommand)
+            .add_sub_option(tmdb_subcommand_group_collection_subcommand);
+    i18n::command("announce", "announce")
         .add_option(anilist_subcommand)
         .add_option(tmdb_subcommand_group)
 }
 
-async fn send_anilist_announcement(handler: &Handler, ctx: &Context, media: Media) -> String {
-    let regex_html = Regex::new(r"<[^>]*>").unwrap();
-    let embed_footer = CreateEmbedFooter::new("Powered by AniList")
-        .icon_url("https://anilist.co/img/icons/android-chrome-512x512.png");
-    let embed = CreateEmbed::new()
-        .title(format!(
-            "{} is now available on Jellyfin!",
-            media.title.english
-        ))
-        .description(regex_html.replace_all(&media.description, ""))
-        .image(media.cover_image.large)
-        .color(decode_hex(&media.cover_image.color))
-        .footer(embed_footer);
-    let message = CreateMessage::new().embed(embed);
-    let message_sent = handler
-        .jellyfin_announcements_channel_id
-        .send_message(&ctx.http, message)
-        .await;
+const ANILIST_FOOTER_ICON: &str = "https://anilist.co/img/icons/android-chrome-512x512.png";
+const TMDB_FOOTER_ICON: &str = "https://www.themoviedb.org/assets/2/favicon-43c40950dbf3cffd5e6d682c5a8986dfdc0ac90dce9f59da9ef072aaf53aebb3.png";
 
-    match message_sent {
-        Ok(_message) => MessageBuilder::new()
-            .push("Announcement sent in ")
-            .mention(&handler.jellyfin_announcements_channel_id)
-            .build(),
-        Err(e) => format!("Cannot post announcement: {}", e).to_string(),
+async fn send_anilist_announcement(
+    handler: &Handler,
+    discord: &dyn DiscordOps,
+    target: &Target<'_>,
+    media: Media,
+) -> Result<String, String> {
+    let title = media.title.preferred(&handler.language).to_string();
+    let embed = anilist_embed(handler, &media);
+    post_announcement(discord, &handler.db, target, embed, MediaKind::Anime, title).await
+}
+
+fn anilist_embed(handler: &Handler, media: &Media) -> CreateEmbed {
+    let title = media.title.preferred(&handler.language);
+    let mut embed = CreateEmbed::new()
+        .title(available(handler, title))
+        .description(synopsis(&media.description, &media.site_url, false))
+        .image(&media.cover_image.large)
+        .color(
+            media
+                .cover_image
+                .color
+                .as_deref()
+                .and_then(parse_color)
+                .unwrap_or(handler.theme.color(Provider::AniList, MediaKind::Anime)),
+        )
+        .footer(footer(handler, "AniList", ANILIST_FOOTER_ICON));
+    if let Some(secondary_language) = &handler.secondary_language {
+        let secondary_title = media.title.preferred(secondary_language);
+        if secondary_title != title {
+            embed = embed.field(
+                tr(&handler.language, "announce.embed.also_known_as"),
+                secondary_title,
+                false,
+            );
+        }
     }
+    embed
 }
 
 async fn send_tmdb_show_announcement(
     handler: &Handler,
-    ctx: &Context,
+    discord: &dyn DiscordOps,
+    target: &Target<'_>,
     config: Configuration,
     tv_show: TvShow,
-) -> String {
-    let embed_footer = CreateEmbedFooter::new("Powered by TMDB")
-    .icon_url("https://www.themoviedb.org/assets/2/favicon-43c40950dbf3cffd5e6d682c5a8986dfdc0ac90dce9f59da9ef072aaf53aebb3.png");
-    let embed = CreateEmbed::new()
-        .title(format!("{} is now available on Jellyfin!", tv_show.name))
-        .description(tv_show.overview)
-        .image(format!(
-            "{}original{}",
-            config.images.secure_base_url, tv_show.poster_path
-        ))
-        .color((13, 37, 63))
-        .footer(embed_footer);
-    let message = CreateMessage::new().embed(embed);
-    let message_sent = handler
-        .jellyfin_announcements_channel_id
-        .send_message(&ctx.http, message)
-        .await;
+) -> Result<String, String> {
+    let embed = tmdb_show_embed(handler, &config, &tv_show).await;
+    post_announcement(
+        discord,
+        &handler.db,
+        target,
+        embed,
+        MediaKind::Show,
+        tv_show.name,
+    )
+    .await
+}
 
-    match message_sent {
-        Ok(_message) => MessageBuilder::new()
-            .push("Announcement sent in ")
-            .mention(&handler.jellyfin_announcements_channel_id)
-            .build(),
-        Err(e) => format!("Cannot post announcement: {e}").to_string(),
+async fn tmdb_show_embed(
+    handler: &Handler,
+    config: &Configuration,
+    tv_show: &TvShow,
+) -> CreateEmbed {
+    let poster_path = tv_show.poster_path.as_deref();
+    let color = tmdb_poster_color(handler, config, poster_path, MediaKind::Show).await;
+    let mut embed = CreateEmbed::new()
+        .title(available(handler, &tv_show.name))
+        .description(synopsis(
+            &tv_show.overview,
+            &format!("https://www.themoviedb.org/tv/{}", tv_show.id),
+            false,
+        ))
+        .color(color)
+        .footer(footer(handler, "TMDB", TMDB_FOOTER_ICON));
+    if let Some(poster_path) = poster_path {
+        embed = embed.image(tmdb_image(config, poster_path));
     }
+    with_secondary_language(handler, embed, &tv_show.translations, false, false)
 }
 
 async fn send_tmdb_movie_announcement(
     handler: &Handler,
-    ctx: &Context,
+    discord: &dyn DiscordOps,
+    target: &Target<'_>,
     config: Configuration,
     movie: Movie,
-) -> String {
-    let embed_footer = CreateEmbedFooter::new("Powered by TMDB")
-    .icon_url("https://www.themoviedb.org/assets/2/favicon-43c40950dbf3cffd5e6d682c5a8986dfdc0ac90dce9f59da9ef072aaf53aebb3.png");
-    let embed = CreateEmbed::new()
-        .title(format!("{} is now available on Jellyfin!", movie.title))
-        .description(movie.overview)
-        .image(format!(
-            "{}original{}",
-            config.images.secure_base_url, movie.poster_path
-        ))
-        .color((13, 37, 63))
-        .footer(embed_footer);
-    let message = CreateMessage::new().embed(embed);
-    let message_sent = handler
-        .jellyfin_announcements_channel_id
-        .send_message(&ctx.http, message)
-        .await;
+) -> Result<String, String> {
+    let embed = tmdb_movie_embed(handler, &config, &movie).await;
+    post_announcement(
+        discord,
+        &handler.db,
+        target,
+        embed,
+        MediaKind::Movie,
+        movie.title,
+    )
+    .await
+}
 
-    match message_sent {
-        Ok(_message) => MessageBuilder::new()
-            .push("Announcement sent in ")
-            .mention(&handler.jellyfin_announcements_channel_id)
-            .build(),
-        Err(e) => format!("Cannot post announcement: {e}").to_string(),
+async fn tmdb_movie_embed(handler: &Handler, config: &Configuration, movie: &Movie) -> CreateEmbed {
+    let poster_path = movie.poster_path.as_deref();
+    let color = tmdb_poster_color(handler, config, poster_path, MediaKind::Movie).await;
+    let mut embed = CreateEmbed::new()
+        .title(available(handler, &movie.title))
+        .description(synopsis(
+            &movie.overview,
+            &format!("https://www.themoviedb.org/movie/{}", movie.id),
+            false,
+        ))
+        .color(color)
+        .footer(footer(handler, "TMDB", TMDB_FOOTER_ICON));
+    if let Some(poster_path) = poster_path {
+        embed = embed.image(tmdb_image(config, poster_path));
     }
+    with_secondary_language(handler, embed, &movie.translations, false, false)
 }
 
 async fn send_tmdb_season_announcement(
     handler: &Handler,
-    ctx: &Context,
+    discord: &dyn DiscordOps,
+    target: &Target<'_>,
     config: Configuration,
     tv_show: TvShow,
     season: Season,
-) -> String {
-    let embed_footer = CreateEmbedFooter::new("Powered by TMDB")
-    .icon_url("https://www.themoviedb.org/assets/2/favicon-43c40950dbf3cffd5e6d682c5a8986dfdc0ac90dce9f59da9ef072aaf53aebb3.png");
-    let embed = CreateEmbed::new()
-        .title(format!(
-            "{} {} is now available on Jellyfin!",
-            tv_show.name, season.name
-        ))
-        .description(season.overview)
-        .image(format!(
-            "{}original{}",
-            config.images.secure_base_url, season.poster_path
+    spoiler_safe: bool,
+) -> Result<String, String> {
+    let embed = tmdb_season_embed(handler, &config, &tv_show, &season, spoiler_safe).await;
+    post_announcement(
+        discord,
+        &handler.db,
+        target,
+        embed,
+        MediaKind::Show,
+        format!("{} {}", tv_show.name, season.name),
+    )
+    .await
+}
+
+async fn tmdb_season_embed(
+    handler: &Handler,
+    config: &Configuration,
+    tv_show: &TvShow,
+    season: &Season,
+    spoiler_safe: bool,
+) -> CreateEmbed {
+    // Not every season has its own poster, the show's poster is the next best thing
+    let poster_path = season
+        .poster_path
+        .as_deref()
+        .or(tv_show.poster_path.as_deref());
+    let color = tmdb_poster_color(handler, config, poster_path, MediaKind::Show).await;
+    let mut embed = CreateEmbed::new()
+        .title(available(
+            handler,
+            &format!("{} {}", tv_show.name, season.name),
         ))
-        .thumbnail(format!(
-            "{}original{}",
-            config.images.secure_base_url, tv_show.poster_path
+        .description(synopsis(
+            &season.overview,
+            &format!(
+                "https://www.themoviedb.org/tv/{}/season/{}",
+                tv_show.id, season.season_number
+            ),
+            spoiler_safe,
         ))
-        .color((13, 37, 63))
-        .footer(embed_footer);
-    let message = CreateMessage::new().embed(embed);
-    let message_sent = handler
-        .jellyfin_announcements_channel_id
-        .send_message(&ctx.http, message)
-        .await;
-
-    match message_sent {
-        Ok(_message) => MessageBuilder::new()
-            .push("Announcement sent in ")
-            .mention(&handler.jellyfin_announcements_channel_id)
-            .build(),
-        Err(e) => format!("Cannot post announcement: {e}").to_string(),
+        .color(color)
+        .footer(footer(handler, "TMDB", TMDB_FOOTER_ICON));
+    if let Some(poster_path) = poster_path {
+        embed = embed.image(tmdb_image(config, poster_path));
     }
+    if let (Some(_), Some(show_poster_path)) = (&season.poster_path, &tv_show.poster_path) {
+        embed = embed.thumbnail(tmdb_image(config, show_poster_path));
+    }
+    with_secondary_language(handler, embed, &season.translations, spoiler_safe, false)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn send_tmdb_episode_announcement(
     handler: &Handler,
-    ctx: &Context,
+    discord: &dyn DiscordOps,
+    target: &Target<'_>,
     config: Configuration,
     tv_show: TvShow,
     season: Season,
     episode: Episode,
+    spoiler_safe: bool,
+) -> Result<String, String> {
+    let embed =
+        tmdb_episode_embed(handler, &config, &tv_show, &season, &episode, spoiler_safe).await;
+    post_announcement(
+        discord,
+        &handler.db,
+        target,
+        embed,
+        MediaKind::Show,
+        episode_title(handler, &tv_show, &season, &episode),
+    )
+    .await
+}
+
+async fn tmdb_episode_embed(
+    handler: &Handler,
+    config: &Configuration,
+    tv_show: &TvShow,
+    season: &Season,
+    episode: &Episode,
+    spoiler_safe: bool,
+) -> CreateEmbed {
+    let poster_path = season
+        .poster_path
+        .as_deref()
+        .or(tv_show.poster_path.as_deref());
+    let color = tmdb_poster_color(handler, config, poster_path, MediaKind::Show).await;
+    let is_finale = episode.episode_type == "finale"
+        || season.episodes.last().map(|last| last.episode_number) == Some(episode.episode_number);
+    // The name of a finale often gives away how the season ends
+    let title = episode_title(handler, tv_show, season, episode);
+    let title = if episode.name.is_empty() || (spoiler_safe && is_finale) {
+        available(handler, &title)
+    } else {
+        available(handler, &format!("{title}: {}", episode.name))
+    };
+    let mut embed = CreateEmbed::new().title(title).color(color).footer(footer(
+        handler,
+        "TMDB",
+        TMDB_FOOTER_ICON,
+    ));
+    if let Some(poster_path) = poster_path {
+        embed = embed.thumbnail(tmdb_image(config, poster_path));
+    }
+    embed = embed.description(synopsis(
+        &episode.overview,
+        &format!(
+            "https://www.themoviedb.org/tv/{}/season/{}/episode/{}",
+            tv_show.id, season.season_number, episode.episode_number
+        ),
+        spoiler_safe,
+    ));
+    if let (false, Some(still_path)) = (spoiler_safe, &episode.still_path) {
+        embed = embed.image(tmdb_image(config, still_path));
+    }
+    with_secondary_language(
+        handler,
+        embed,
+        &episode.translations,
+        spoiler_safe,
+        spoiler_safe && is_finale,
+    )
+}
+
+/// Leaves out the parts of a collection that are not in the Jellyfin library.
+async fn only_in_library(
+    handler: &Handler,
+    mut collection: Collection,
+    locale: &str,
+) -> Result<Collection, String> {
+    let Some(jellyfin) = &handler.jellyfin else {
+        return Err(tr(locale, "announce.jellyfin_not_configured").to_string());
+    };
+    let library_ids = jellyfin.get_movie_tmdb_ids().await?;
+    collection
+        .parts
+        .retain(|part| library_ids.contains(&part.id));
+    if collection.parts.is_empty() {
+        return Err(tr(locale, "announce.collection_not_in_library").to_string());
+    }
+    Ok(collection)
+}
+
+async fn send_tmdb_collection_announcement(
+    handler: &Handler,
+    discord: &dyn DiscordOps,
+    target: &Target<'_>,
+    config: Configuration,
+    collection: Collection,
+) -> Result<String, String> {
+    let embed = tmdb_collection_embed(handler, &config, &collection).await;
+    post_announcement(
+        discord,
+        &handler.db,
+        target,
+        embed,
+        MediaKind::Movie,
+        collection.name,
+    )
+    .await
+}
+
+async fn tmdb_collection_embed(
+    handler: &Handler,
+    config: &Configuration,
+    collection: &Collection,
+) -> CreateEmbed {
+    // Parts without a release date are unreleased, list them last
+    let mut parts: Vec<&CollectionPart> = collection.parts.iter().collect();
+    parts.sort_by_key(|part| (part.year().is_none(), part.release_date.clone()));
+    let films = parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| match part.year() {
+            Some(year) => format!("{}. {} ({})", i + 1, part.title, year),
+            None => format!("{}. {}", i + 1, part.title),
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+    let read_more_url = format!("https://www.themoviedb.org/collection/{}", collection.id);
+    let films = truncate(&films, DESCRIPTION_LIMIT / 2, Some(&read_more_url));
+    let overview = sanitize(
+        &collection.overview,
+        Some(&read_more_url),
+        DESCRIPTION_LIMIT - films.chars().count() - 2,
+    );
+    let description = [overview, films]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n\n");
+
+    let color = tmdb_poster_color(
+        handler,
+        config,
+        collection.poster_path.as_deref(),
+        MediaKind::Movie,
+    )
+    .await;
+
+    let mut embed = CreateEmbed::new()
+        .title(available(handler, &collection.name))
+        .description(description)
+        .color(color)
+        .footer(footer(handler, "TMDB", TMDB_FOOTER_ICON));
+    if let Some(image_path) = collection
+        .backdrop_path
+        .as_deref()
+        .or(collection.poster_path.as_deref())
+    {
+        embed = embed.image(tmdb_image(config, image_path));
+    }
+    with_secondary_language(handler, embed, &collection.translations, false, false)
+}
+
+/// Embed title announcing `title`, in the announcement language. Long titles are shortened so
+/// the whole embed title stays within the limit.
+fn available(handler: &Handler, title: &str) -> String {
+    let key = "announce.embed.available";
+    let around = tr_args(&handler.language, key, &[("title", "")])
+        .chars()
+        .count();
+    let title = truncate(title, TITLE_LIMIT.saturating_sub(around), None);
+    tr_args(&handler.language, key, &[("title", &title)])
+}
+
+/// Name of an episode like "Show Season 1 Episode 8", in the announcement language.
+fn episode_title(
+    handler: &Handler,
+    tv_show: &TvShow,
+    season: &Season,
+    episode: &Episode,
 ) -> String {
-    let embed_footer = CreateEmbedFooter::new("Powered by TMDB")
-    .icon_url("https://www.themoviedb.org/assets/2/favicon-43c40950dbf3cffd5e6d682c5a8986dfdc0ac90dce9f59da9ef072aaf53aebb3.png");
-    let embed = CreateEmbed::new()
-        .title(format!(
-            "{} {} Episode {} is now available on Jellyfin!",
-            tv_show.name, season.name, episode.episode_number
-        ))
-        .description(episode.overview)
-        .image(format!(
-            "{}original{}",
-            config.images.secure_base_url, episode.still_path
-        ))
-        .thumbnail(format!(
-            "{}original{}",
-            config.images.secure_base_url, season.poster_path
-        ))
-        .color((13, 37, 63))
-        .footer(embed_footer);
+    tr_args(
+        &handler.language,
+        "announce.embed.episode",
+        &[
+            ("show", &tv_show.name),
+            ("season", &season.name),
+            ("number", &episode.episode_number.to_string()),
+        ],
+    )
+}
+
+fn footer(handler: &Handler, provider: &str, icon_url: &str) -> CreateEmbedFooter {
+    let text = tr_args(
+        &handler.language,
+        "announce.embed.powered_by",
+        &[("provider", provider)],
+    );
+    CreateEmbedFooter::new(text).icon_url(icon_url)
+}
+
+/// Embed description for a synopsis, optionally hidden behind a spoiler.
+fn synopsis(text: &str, read_more_url: &str, spoiler_safe: bool) -> String {
+    if spoiler_safe {
+        // Leave room for the spoiler tags and escaped pipes
+        let limit = DESCRIPTION_LIMIT - 4 - text.matches('|').count();
+        spoiler(&sanitize(text, Some(read_more_url), limit))
+    } else {
+        sanitize(text, Some(read_more_url), DESCRIPTION_LIMIT)
+    }
+}
+
+/// Adds the title and overview in the secondary language as a field, if one is configured and
+/// TMDB has a translation for it. Without a translated title, or with `hide_title`, the field is
+/// named after the language instead, and left out when that has no name.
+fn with_secondary_language(
+    handler: &Handler,
+    embed: CreateEmbed,
+    translations: &Translations,
+    spoiler_safe: bool,
+    hide_title: bool,
+) -> CreateEmbed {
+    let Some(language) = &handler.secondary_language else {
+        return embed;
+    };
+    match translations.get(language) {
+        Some(data) if !data.overview.is_empty() => {
+            let name = if data.title.is_empty() || hide_title {
+                match i18n::language_name(&handler.language, language) {
+                    Some(name) => name,
+                    None => return embed,
+                }
+            } else {
+                data.title.as_str()
+            };
+            let limit = FIELD_VALUE_LIMIT - 4 - data.overview.matches('|').count();
+            let overview = sanitize(&data.overview, None, limit);
+            embed.field(
+                truncate(name, FIELD_NAME_LIMIT, None),
+                if spoiler_safe {
+                    spoiler(&overview)
+                } else {
+                    overview
+                },
+                false,
+            )
+        }
+        _ => embed,
+    }
+}
+
+/// Wraps text in Discord spoiler tags, escaping any pipes that would close the spoiler early.
+fn spoiler(text: &str) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    format!("||{}||", text.replace('|', "\\|"))
+}
+
+fn bool_option(options: &[ResolvedOption], name: &str) -> Option<bool> {
+    options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| match option.value {
+            ResolvedValue::Boolean(value) => Some(value),
+            _ => None,
+        })
+}
+
+/// Full size rendition of a TMDB image.
+fn tmdb_image(config: &Configuration, path: &str) -> String {
+    format!("{}original{}", config.images.secure_base_url, path)
+}
+
+/// Colour of the poster, falling back to the theme colour when there is no poster or its colour
+/// cannot be determined.
+async fn tmdb_poster_color(
+    handler: &Handler,
+    config: &Configuration,
+    poster_path: Option<&str>,
+    kind: MediaKind,
+) -> Rgb {
+    let fallback = handler.theme.color(Provider::Tmdb, kind);
+    let Some(poster_path) = poster_path else {
+        return fallback;
+    };
+    // A small rendition is plenty to pick a colour from
+    let url = format!("{}w185{}", config.images.secure_base_url, poster_path);
+    handler.poster_colors.get(&url).await.unwrap_or(fallback)
+}
+
+async fn post_announcement(
+    discord: &dyn DiscordOps,
+    db: &Database,
+    target: &Target<'_>,
+    embed: CreateEmbed,
+    kind: MediaKind,
+    title: String,
+) -> Result<String, String> {
     let message = CreateMessage::new().embed(embed);
-    let message_sent = handler
-        .jellyfin_announcements_channel_id
-        .send_message(&ctx.http, message)
-        .await;
+    let message_sent = discord.send_message(target.channel_id, message).await;
+    let action = format!("Announced {} {title}", kind.as_str());
 
     match message_sent {
-        Ok(_message) => MessageBuilder::new()
-            .push("Announcement sent in ")
-            .mention(&handler.jellyfin_announcements_channel_id)
-            .build(),
-        Err(e) => format!("Cannot post announcement: {e}").to_string(),
+        Ok(link) => {
+            // The announcement is out, failing to remember it only affects the digest
+            if let Err(e) =
+                db.record_announcement(target.guild_id, kind, &title, Utc::now().timestamp())
+            {
+                error!(error = %e, "Cannot record announcement");
+            }
+            METRICS
+                .announcements_posted
+                .with_label_values(&[kind.as_str()])
+                .inc();
+            let entry = audit::entry(target.user_id, &action, Outcome::Success(link));
+            audit::log(discord, db, target.guild_id, vec![entry]).await;
+            Ok(tr_args(
+                target.locale,
+                "announce.sent",
+                &[("channel", &Mention::from(target.channel_id).to_string())],
+            ))
+        }
+        Err(e) => {
+            let entry = audit::entry(target.user_id, &action, Outcome::Failure(e.clone()));
+            audit::log(discord, db, target.guild_id, vec![entry]).await;
+            Err(tr_args(target.locale, "announce.failed", &[("error", &e)]))
+        }
+    }
+}
+
+pub struct Announce;
+
+#[async_trait]
+impl SlashCommand for Announce {
+    fn name(&self) -> &'static str {
+        "announce"
+    }
+
+    fn permissions(&self) -> Permissions {
+        Permissions::MANAGE_MESSAGES
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        command: &CommandInteraction,
+        handler: &Handler,
+        ctx: &Context,
+    ) -> Result<String, String> {
+        run(command, handler, ctx).await
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,536 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use image::{ImageFormat, Rgb as Pixel, RgbImage};
+use serde_json::Value;
+use wiremock::matchers::{body_partial_json, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use crate::api;
+use crate::db::GuildSettings;
+use crate::discord::fake::FakeDiscord;
+
+use super::*;
+
+const ANNOUNCEMENTS: u64 = 30;
+const AUDIT: u64 = 31;
+
+/// Where TMDB serves images from, the stub server serves them in tests.
+const TMDB_IMAGES: &str = "https://image.tmdb.org";
+const SHOW_POSTER: &str = "/yueXS3q8BtoWekcvdMFkRfFMaA1.jpg";
+const MOVIE_POSTER: &str = "/vfrQk5IPloGg1v9Rzbh2Eg3VGyM.jpg";
+const SEASON_POSTER: &str = "/2Gn8JQ6gKQT6S1l0M5cS1WkOaJx.jpg";
+
+fn target() -> Target<'static> {
+    Target {
+        locale: "en-US",
+        guild_id: GuildId::new(1),
+        channel_id: ChannelId::new(ANNOUNCEMENTS),
+        user_id: UserId::new(99),
+    }
+}
+
+fn fixture(name: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    let json = std::fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("Cannot read {}: {e}", path.display()));
+    serde_json::from_str(&json).unwrap()
+}
+
+/// A poster in a single colour.
+fn poster(color: [u8; 3]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    RgbImage::from_pixel(8, 12, Pixel(color))
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .unwrap();
+    bytes
+}
+
+/// A stub of TMDB and AniList serving the recorded fixtures.
+async fn stub_server() -> MockServer {
+    let server = MockServer::start().await;
+
+    // Images are served by the stub too, so poster colours don't depend on the network
+    let mut configuration = fixture("tmdb/configuration.json");
+    configuration["images"]["secure_base_url"] = format!("{}/t/p/", server.uri()).into();
+    Mock::given(method("GET"))
+        .and(path("/configuration"))
+        .and(header("Authorization", "Bearer token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(configuration))
+        .mount(&server)
+        .await;
+
+    for (endpoint, name) in [
+        ("/movie/348", "tmdb/movie.json"),
+        ("/movie/1408213", "tmdb/movie_without_poster.json"),
+        ("/tv/157239", "tmdb/tv.json"),
+        ("/tv/157239/season/1", "tmdb/season.json"),
+        ("/tv/157239/season/1/episode/8", "tmdb/episode.json"),
+        (
+            "/tv/157239/season/1/episode/1",
+            "tmdb/episode_with_still.json",
+        ),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(endpoint))
+            .and(header("Authorization", "Bearer token"))
+            .and(query_param("language", "en-US"))
+            .and(query_param("append_to_response", "translations"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture(name)))
+            .mount(&server)
+            .await;
+    }
+
+    Mock::given(method("GET"))
+        .and(path("/collection/8091"))
+        .and(query_param("language", "en-US"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(fixture("tmdb/collection.json")))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/collection/8091/translations"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(fixture("tmdb/collection_translations.json")),
+        )
+        .mount(&server)
+        .await;
+
+    // Jellyfin has Alien, but not its sequels
+    Mock::given(method("GET"))
+        .and(path("/Items"))
+        .and(header("X-Emby-Token", "key"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "Items": [
+                { "ProviderIds": { "Tmdb": "348" } },
+                { "ProviderIds": {} }
+            ]
+        })))
+        .mount(&server)
+        .await;
+
+    for (poster_path, color) in [
+        (MOVIE_POSTER, [28, 92, 60]),
+        (SHOW_POSTER, [200, 40, 40]),
+        (SEASON_POSTER, [40, 90, 200]),
+    ] {
+        Mock::given(method("GET"))
+            .and(path(format!("/t/p/w185{poster_path}")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(poster(color)))
+            .mount(&server)
+            .await;
+    }
+
+    for (id, name) in [
+        (21, "anilist/media.json"),
+        (1, "anilist/media_without_color.json"),
+    ] {
+        Mock::given(method("POST"))
+            .and(path("/"))
+            .and(body_partial_json(
+                serde_json::json!({ "variables": { "id": id } }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(fixture(name)))
+            .mount(&server)
+            .await;
+    }
+
+    server
+}
+
+fn handler(server: &MockServer) -> Handler {
+    Handler {
+        secondary_language: Some("nl-NL".to_string()),
+        ..Handler::for_tests(&server.uri())
+    }
+}
+
+/// Compares an embed to `tests/golden/announce/<name>.json`, run with `UPDATE_GOLDEN=1` to
+/// (re)write the golden files instead.
+fn assert_golden(name: &str, server: &MockServer, embed: &CreateEmbed) {
+    let actual = serde_json::to_string_pretty(embed)
+        .unwrap()
+        .replace(&server.uri(), TMDB_IMAGES);
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden/announce")
+        .join(format!("{name}.json"));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, format!("{actual}\n")).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "Missing {}, run with UPDATE_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+    assert_eq!(
+        actual,
+        expected.trim_end(),
+        "{name} differs from its golden file"
+    );
+}
+
+#[tokio::test]
+async fn movie_embed() {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let config = handler.tmdb.get_configuration().await.unwrap();
+    let movie = handler.tmdb.get_movie(&348, "en-US").await.unwrap();
+
+    let embed = tmdb_movie_embed(&handler, &config, &movie).await;
+
+    assert_golden("movie", &server, &embed);
+}
+
+#[tokio::test]
+async fn movie_in_dutch_embed() {
+    let server = stub_server().await;
+    let handler = Handler {
+        language: "nl-NL".to_string(),
+        secondary_language: Some("en-US".to_string()),
+        ..Handler::for_tests(&server.uri())
+    };
+    let config = handler.tmdb.get_configuration().await.unwrap();
+    let movie = handler.tmdb.get_movie(&348, "en-US").await.unwrap();
+
+    let embed = tmdb_movie_embed(&handler, &config, &movie).await;
+
+    assert_golden("movie_in_dutch", &server, &embed);
+}
+
+#[tokio::test]
+async fn movie_without_poster_embed() {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let config = handler.tmdb.get_configuration().await.unwrap();
+    let movie = handler.tmdb.get_movie(&1408213, "en-US").await.unwrap();
+
+    let embed = tmdb_movie_embed(&handler, &config, &movie).await;
+
+    assert_golden("movie_without_poster", &server, &embed);
+}
+
+#[tokio::test]
+async fn show_embed() {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let config = handler.tmdb.get_configuration().await.unwrap();
+    let tv_show = handler.tmdb.get_tv_show(&157239, "en-US").await.unwrap();
+
+    let embed = tmdb_show_embed(&handler, &config, &tv_show).await;
+
+    assert_golden("show", &server, &embed);
+}
+
+#[tokio::test]
+async fn season_without_poster_embed() {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let config = handler.tmdb.get_configuration().await.unwrap();
+    let tv_show = handler.tmdb.get_tv_show(&157239, "en-US").await.unwrap();
+    let season = handler.tmdb.get_season(&157239, &1, "en-US").await.unwrap();
+
+    let embed = tmdb_season_embed(&handler, &config, &tv_show, &season, false).await;
+
+    assert_golden("season_without_poster", &server, &embed);
+}
+
+#[tokio::test]
+async fn season_embed() {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let config = handler.tmdb.get_configuration().await.unwrap();
+    let tv_show = handler.tmdb.get_tv_show(&157239, "en-US").await.unwrap();
+    let season: Season = serde_json::from_value(fixture("tmdb/season_with_poster.json")).unwrap();
+
+    let embed = tmdb_season_embed(&handler, &config, &tv_show, &season, false).await;
+
+    assert_golden("season", &server, &embed);
+}
+
+async fn episode_with_still_embed(spoiler_safe: bool) -> (MockServer, CreateEmbed) {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let config = handler.tmdb.get_configuration().await.unwrap();
+    let tv_show = handler.tmdb.get_tv_show(&157239, "en-US").await.unwrap();
+    let season = handler.tmdb.get_season(&157239, &1, "en-US").await.unwrap();
+    let episode = handler
+        .tmdb
+        .get_episode(&157239, &1, &1, "en-US")
+        .await
+        .unwrap();
+
+    let embed =
+        tmdb_episode_embed(&handler, &config, &tv_show, &season, &episode, spoiler_safe).await;
+    (server, embed)
+}
+
+#[tokio::test]
+async fn episode_embed() {
+    let (server, embed) = episode_with_still_embed(false).await;
+
+    assert_golden("episode", &server, &embed);
+}
+
+#[tokio::test]
+async fn spoiler_safe_episode_embed() {
+    let (server, embed) = episode_with_still_embed(true).await;
+
+    // The still is left out, the title is kept as this is not the finale
+    assert_golden("spoiler_safe_episode", &server, &embed);
+}
+
+#[tokio::test]
+async fn episode_without_still_embed() {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let config = handler.tmdb.get_configuration().await.unwrap();
+    let tv_show = handler.tmdb.get_tv_show(&157239, "en-US").await.unwrap();
+    let season = handler.tmdb.get_season(&157239, &1, "en-US").await.unwrap();
+    let episode = handler
+        .tmdb
+        .get_episode(&157239, &1, &8, "en-US")
+        .await
+        .unwrap();
+
+    let embed = tmdb_episode_embed(&handler, &config, &tv_show, &season, &episode, false).await;
+
+    assert_golden("episode_without_still", &server, &embed);
+}
+
+#[tokio::test]
+async fn long_titles_are_shortened() {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let config = handler.tmdb.get_configuration().await.unwrap();
+    let tv_show = handler.tmdb.get_tv_show(&157239, "en-US").await.unwrap();
+    let season = handler.tmdb.get_season(&157239, &1, "en-US").await.unwrap();
+    let mut episode = handler
+        .tmdb
+        .get_episode(&157239, &1, &8, "en-US")
+        .await
+        .unwrap();
+    episode.name = "Lost Boy ".repeat(40);
+
+    let embed = tmdb_episode_embed(&handler, &config, &tv_show, &season, &episode, false).await;
+
+    let title = serde_json::to_value(embed).unwrap()["title"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert!(title.chars().count() <= TITLE_LIMIT, "{title}");
+    assert!(
+        title.ends_with("… is now available on Jellyfin!"),
+        "{title}"
+    );
+}
+
+#[tokio::test]
+async fn spoiler_safe_finale_embed() {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let config = handler.tmdb.get_configuration().await.unwrap();
+    let tv_show = handler.tmdb.get_tv_show(&157239, "en-US").await.unwrap();
+    let season = handler.tmdb.get_season(&157239, &1, "en-US").await.unwrap();
+    let episode = handler
+        .tmdb
+        .get_episode(&157239, &1, &8, "en-US")
+        .await
+        .unwrap();
+
+    let embed = tmdb_episode_embed(&handler, &config, &tv_show, &season, &episode, true).await;
+
+    assert_golden("spoiler_safe_finale", &server, &embed);
+}
+
+#[tokio::test]
+async fn collection_embed() {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let config = handler.tmdb.get_configuration().await.unwrap();
+    let collection = handler.tmdb.get_collection(&8091, "en-US").await.unwrap();
+
+    let embed = tmdb_collection_embed(&handler, &config, &collection).await;
+
+    assert_golden("collection", &server, &embed);
+}
+
+#[tokio::test]
+async fn collection_without_overview_embed() {
+    let server = stub_server().await;
+    let handler = Handler {
+        secondary_language: None,
+        ..handler(&server)
+    };
+    let config = handler.tmdb.get_configuration().await.unwrap();
+    let mut collection = handler.tmdb.get_collection(&8091, "en-US").await.unwrap();
+    collection.overview.clear();
+
+    let embed = tmdb_collection_embed(&handler, &config, &collection).await;
+
+    let embed = serde_json::to_value(embed).unwrap();
+    assert_eq!(
+        embed["description"],
+        "1. Alien (1979)\n2. Aliens (1986)\n3. Untitled Alien Sequel"
+    );
+}
+
+#[tokio::test]
+async fn collections_only_list_films_in_the_library() {
+    let server = stub_server().await;
+    let handler = Handler {
+        jellyfin: Some(api::jellyfin::Client::new(server.uri(), "key".to_string())),
+        ..handler(&server)
+    };
+    let collection = handler.tmdb.get_collection(&8091, "en-US").await.unwrap();
+
+    let collection = only_in_library(&handler, collection, "en-US")
+        .await
+        .unwrap();
+
+    let ids: Vec<i64> = collection.parts.iter().map(|part| part.id).collect();
+    assert_eq!(ids, vec![348]);
+}
+
+#[tokio::test]
+async fn collections_in_the_library_need_jellyfin() {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let collection = handler.tmdb.get_collection(&8091, "en-US").await.unwrap();
+
+    let error = only_in_library(&handler, collection, "en-US")
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        error,
+        "Jellyfin is not configured, cannot check the library"
+    );
+}
+
+#[tokio::test]
+async fn anime_embed() {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let media = handler.anilist.get_media(&21).await.unwrap();
+
+    assert_golden("anime", &server, &anilist_embed(&handler, &media));
+}
+
+#[tokio::test]
+async fn anime_without_color_embed() {
+    let server = stub_server().await;
+    let handler = handler(&server);
+    let media = handler.anilist.get_media(&1).await.unwrap();
+
+    assert_golden(
+        "anime_without_color",
+        &server,
+        &anilist_embed(&handler, &media),
+    );
+}
+
+#[tokio::test]
+async fn posts_and_records_the_announcement() {
+    let discord = FakeDiscord::default();
+    let db = Database::open(":memory:").unwrap();
+    let embed = CreateEmbed::new().title("Alien is now available on Jellyfin!");
+
+    let reply = post_announcement(
+        &discord,
+        &db,
+        &target(),
+        embed,
+        MediaKind::Movie,
+        "Alien".to_string(),
+    )
+    .await;
+
+    assert_eq!(
+        reply,
+        Ok(format!("Announcement sent in <#{ANNOUNCEMENTS}>"))
+    );
+    let messages = discord.messages_in(ChannelId::new(ANNOUNCEMENTS));
+    assert_eq!(messages.len(), 1);
+    assert_eq!(
+        messages[0]["embeds"][0]["title"],
+        "Alien is now available on Jellyfin!"
+    );
+    let announcements = db.announcements_since(GuildId::new(1), 0).unwrap();
+    assert_eq!(announcements.len(), 1);
+    assert_eq!(announcements[0].title, "Alien");
+}
+
+#[tokio::test]
+async fn logs_the_announcement_to_the_audit_channel() {
+    let discord = FakeDiscord::default();
+    let db = Database::open(":memory:").unwrap();
+    let mut settings = GuildSettings::new(GuildId::new(1));
+    settings.audit_channel_id = Some(ChannelId::new(AUDIT));
+    db.save_guild_settings(&settings).unwrap();
+
+    post_announcement(
+        &discord,
+        &db,
+        &target(),
+        CreateEmbed::new(),
+        MediaKind::Show,
+        "Alien: Earth".to_string(),
+    )
+    .await
+    .unwrap();
+
+    let audit = discord.messages_in(ChannelId::new(AUDIT));
+    assert_eq!(audit.len(), 1);
+    assert_eq!(
+        audit[0]["embeds"][0]["title"],
+        "Announced show Alien: Earth"
+    );
+}
+
+#[tokio::test]
+async fn reports_a_failed_announcement() {
+    let discord = FakeDiscord::default();
+    discord.state.lock().unwrap().fail_messages = true;
+    let db = Database::open(":memory:").unwrap();
+
+    let reply = post_announcement(
+        &discord,
+        &db,
+        &target(),
+        CreateEmbed::new(),
+        MediaKind::Movie,
+        "Alien".to_string(),
+    )
+    .await;
+
+    assert!(reply.is_err());
+    assert!(db
+        .announcements_since(GuildId::new(1), 0)
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn reports_a_missing_collection() {
+    let server = stub_server().await;
+    Mock::given(method("GET"))
+        .and(path("/collection/1"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+            "status_code": 34,
+            "status_message": "The resource you requested could not be found."
+        })))
+        .mount(&server)
+        .await;
+
+    let error = handler(&server)
+        .tmdb
+        .get_collection(&1, "en-US")
+        .await
+        .unwrap_err();
+
+    assert!(error.contains("404"), "{error}");
+}
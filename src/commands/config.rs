@@ -0,0 +1,391 @@
+use serenity::async_trait;
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::model::application::{
+    CommandInteraction, CommandOptionType, ResolvedOption, ResolvedValue,
+};
+use serenity::model::channel::ChannelType;
+use serenity::model::mention::Mention;
+use serenity::model::permissions::Permissions;
+use serenity::model::prelude::{ChannelId, RoleId};
+use serenity::prelude::Context;
+
+use super::{SlashCommand, PERMISSION_SCOPES};
+use crate::db::{Database, GuildSettings};
+use crate::i18n::{self, tr, tr_args};
+use crate::Handler;
+
+async fn run(command: &CommandInteraction, handler: &Handler) -> Result<String, String> {
+    let options: &[ResolvedOption] = &command.data.options();
+    let locale = command.locale.as_str();
+
+    let mut settings = super::guild_settings(command, handler)?;
+
+    let subcommand = options.first().expect("Expected subcommand");
+
+    match (subcommand.name, &subcommand.value) {
+        ("show", _) => {
+            let command_roles = handler.db.command_roles(settings.guild_id)?;
+            Ok(show(locale, &settings, &command_roles))
+        }
+        ("roles", ResolvedValue::SubCommandGroup(options)) => {
+            let Some(ResolvedOption {
+                name: action,
+                value: ResolvedValue::SubCommand(options),
+                ..
+            }) = options.first()
+            else {
+                return Err(tr(locale, "common.invalid_subcommand").to_string());
+            };
+            let (mut scope, mut role) = (None, None);
+            for option in options {
+                match (option.name, &option.value) {
+                    ("command", ResolvedValue::String(value)) => scope = Some(*value),
+                    ("role", ResolvedValue::Role(value)) => role = Some(value.id),
+                    _ => {}
+                }
+            }
+            let (Some(scope), Some(role_id)) = (scope, role) else {
+                return Err(tr(locale, "common.invalid_id").to_string());
+            };
+            if !PERMISSION_SCOPES.contains(&scope) {
+                return Err(tr(locale, "common.invalid_command").to_string());
+            }
+
+            let args = [
+                ("command", scope),
+                ("role", &Mention::from(role_id).to_string()),
+            ];
+            match *action {
+                "allow" => match handler
+                    .db
+                    .add_command_role(settings.guild_id, scope, role_id)
+                {
+                    Ok(()) => Ok(tr_args(locale, "config.roles.allowed", &args)),
+                    Err(e) => Err(e),
+                },
+                "disallow" => {
+                    match handler
+                        .db
+                        .remove_command_role(settings.guild_id, scope, role_id)
+                    {
+                        Ok(true) => Ok(tr_args(locale, "config.roles.disallowed", &args)),
+                        Ok(false) => Err(tr_args(locale, "config.roles.not_allowed", &args)),
+                        Err(e) => Err(e),
+                    }
+                }
+                _ => Err(tr(locale, "common.invalid_subcommand").to_string()),
+            }
+        }
+        ("set", ResolvedValue::SubCommandGroup(options)) => {
+            let Some(key_option) = options.first() else {
+                return Err(tr(locale, "common.invalid_subcommand").to_string());
+            };
+            let channel_id = match &key_option.value {
+                ResolvedValue::SubCommand(options) => match options.first() {
+                    Some(ResolvedOption {
+                        value: ResolvedValue::Channel(channel),
+                        ..
+                    }) => channel.id,
+                    _ => return Err(tr(locale, "common.invalid_id").to_string()),
+                },
+                _ => return Err(tr(locale, "common.invalid_subcommand").to_string()),
+            };
+            update(
+                &handler.db,
+                &mut settings,
+                key_option.name,
+                Some(channel_id),
+                locale,
+            )
+        }
+        ("unset", ResolvedValue::SubCommand(options)) => {
+            let Some(ResolvedOption {
+                value: ResolvedValue::String(key),
+                ..
+            }) = options.first()
+            else {
+                return Err(tr(locale, "common.invalid_subcommand").to_string());
+            };
+            update(&handler.db, &mut settings, key, None, locale)
+        }
+        _ => Err(tr(locale, "common.invalid_subcommand").to_string()),
+    }
+}
+
+/// Settings that hold a channel, offered by `/config unset`.
+const KEYS: &[&str] = &[
+    "announcements_channel",
+    "shuffle_category",
+    "lobby_channel",
+    "audit_channel",
+];
+
+fn setting<'a>(settings: &'a mut GuildSettings, key: &str) -> Option<&'a mut Option<ChannelId>> {
+    match key {
+        "announcements_channel" => Some(&mut settings.announcements_channel_id),
+        "shuffle_category" => Some(&mut settings.shuffle_category_id),
+        "lobby_channel" => Some(&mut settings.lobby_channel_id),
+        "audit_channel" => Some(&mut settings.audit_channel_id),
+        _ => None,
+    }
+}
+
+/// Sets the setting `key` to `value`, or unsets it when `value` is `None`.
+fn update(
+    db: &Database,
+    settings: &mut GuildSettings,
+    key: &str,
+    value: Option<ChannelId>,
+    locale: &str,
+) -> Result<String, String> {
+    let Some(setting) = setting(settings, key) else {
+        return Err(tr(locale, "common.invalid_subcommand").to_string());
+    };
+    *setting = value;
+
+    // Commands read the settings from the database every time, so this applies right away
+    db.save_guild_settings(settings)?;
+    Ok(match value {
+        Some(channel_id) => tr_args(
+            locale,
+            "config.updated",
+            &[
+                ("key", key),
+                ("value", &Mention::from(channel_id).to_string()),
+            ],
+        ),
+        None => tr_args(locale, "config.cleared", &[("key", key)]),
+    })
+}
+
+fn show(locale: &str, settings: &GuildSettings, command_roles: &[(String, RoleId)]) -> String {
+    let channel = |channel_id: Option<ChannelId>| match channel_id {
+        Some(channel_id) => Mention::from(channel_id).to_string(),
+        None => tr(locale, "config.not_set").to_string(),
+    };
+
+    let mut lines: Vec<String> = [
+        (
+            "announcements_channel",
+            channel(settings.announcements_channel_id),
+        ),
+        ("shuffle_category", channel(settings.shuffle_category_id)),
+        ("lobby_channel", channel(settings.lobby_channel_id)),
+        ("audit_channel", channel(settings.audit_channel_id)),
+    ]
+    .iter()
+    .map(|(key, value)| format!("**{key}**: {value}"))
+    .collect();
+
+    lines.push(format!("\n**{}**", tr(locale, "config.roles.heading")));
+    for scope in PERMISSION_SCOPES {
+        let roles: Vec<String> = command_roles
+            .iter()
+            .filter(|(command, _)| command == scope)
+            .map(|(_, role_id)| Mention::from(*role_id).to_string())
+            .collect();
+        if !roles.is_empty() {
+            lines.push(format!("`/{scope}`: {}", roles.join(", ")));
+        }
+    }
+    if command_roles.is_empty() {
+        lines.push(tr(locale, "config.roles.default").to_string());
+    }
+
+    lines.join("\n")
+}
+
+/// Subcommand of `roles` taking the command and the role to (dis)allow.
+fn roles_subcommand(name: &str) -> CreateCommandOption {
+    let mut command_option =
+        i18n::option(CommandOptionType::String, "command", "config.roles.command").required(true);
+    for scope in PERMISSION_SCOPES {
+        command_option = command_option.add_string_choice(format!("/{scope}"), *scope);
+    }
+
+    i18n::option(
+        CommandOptionType::SubCommand,
+        name,
+        &format!("config.roles.{name}"),
+    )
+    .add_sub_option(command_option)
+    .add_sub_option(
+        i18n::option(CommandOptionType::Role, "role", "config.roles.role").required(true),
+    )
+}
+
+/// Subcommand of `set` for a setting holding a channel of one of `channel_types`.
+fn set_channel_subcommand(name: &str, channel_types: Vec<ChannelType>) -> CreateCommandOption {
+    i18n::option(
+        CommandOptionType::SubCommand,
+        name,
+        &format!("config.set.{name}"),
+    )
+    .add_sub_option(
+        i18n::option(CommandOptionType::Channel, "value", "config.set.value")
+            .channel_types(channel_types)
+            .required(true),
+    )
+}
+
+fn register() -> CreateCommand {
+    let show_subcommand = i18n::option(CommandOptionType::SubCommand, "show", "config.show");
+    let set_subcommand_group =
+        i18n::option(CommandOptionType::SubCommandGroup, "set", "config.set")
+            .add_sub_option(set_channel_subcommand(
+                "announcements_channel",
+                vec![ChannelType::Text, ChannelType::News],
+            ))
+            .add_sub_option(set_channel_subcommand(
+                "shuffle_category",
+                vec![ChannelType::Category],
+            ))
+            .add_sub_option(set_channel_subcommand(
+                "lobby_channel",
+                vec![ChannelType::Voice],
+            ))
+            .add_sub_option(set_channel_subcommand(
+                "audit_channel",
+                vec![ChannelType::Text],
+            ));
+
+    let mut key_option =
+        i18n::option(CommandOptionType::String, "key", "config.unset.key").required(true);
+    for key in KEYS {
+        key_option = key_option.add_string_choice(*key, *key);
+    }
+    let unset_subcommand = i18n::option(CommandOptionType::SubCommand, "unset", "config.unset")
+        .add_sub_option(key_option);
+
+    let roles_subcommand_group =
+        i18n::option(CommandOptionType::SubCommandGroup, "roles", "config.roles")
+            .add_sub_option(roles_subcommand("allow"))
+            .add_sub_option(roles_subcommand("disallow"));
+
+    i18n::command("config", "config")
+        .add_option(show_subcommand)
+        .add_option(set_subcommand_group)
+        .add_option(unset_subcommand)
+        .add_option(roles_subcommand_group)
+}
+
+pub struct Config;
+
+#[async_trait]
+impl SlashCommand for Config {
+    fn name(&self) -> &'static str {
+        "config"
+    }
+
+    fn permissions(&self) -> Permissions {
+        Permissions::MANAGE_GUILD
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        command: &CommandInteraction,
+        handler: &Handler,
+        _ctx: &Context,
+    ) -> Result<String, String> {
+        run(command, handler).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::model::prelude::GuildId;
+
+    use super::*;
+
+    fn guild() -> (Database, GuildSettings) {
+        let db = Database::open(":memory:").unwrap();
+        let settings = db.guild_settings(GuildId::new(1)).unwrap();
+        (db, settings)
+    }
+
+    #[test]
+    fn set_saves_the_channel() {
+        let (db, mut settings) = guild();
+
+        let reply = update(
+            &db,
+            &mut settings,
+            "audit_channel",
+            Some(ChannelId::new(31)),
+            "en-US",
+        );
+
+        assert_eq!(reply.unwrap(), "audit_channel is now <#31>");
+        let saved = db.guild_settings(GuildId::new(1)).unwrap();
+        assert_eq!(saved.audit_channel_id, Some(ChannelId::new(31)));
+        assert_eq!(saved.lobby_channel_id, None);
+    }
+
+    #[test]
+    fn unset_clears_the_channel() {
+        let (db, mut settings) = guild();
+        update(
+            &db,
+            &mut settings,
+            "audit_channel",
+            Some(ChannelId::new(31)),
+            "en-US",
+        )
+        .unwrap();
+
+        let reply = update(&db, &mut settings, "audit_channel", None, "en-US");
+
+        assert_eq!(reply.unwrap(), "audit_channel is no longer set");
+        let saved = db.guild_settings(GuildId::new(1)).unwrap();
+        assert_eq!(saved.audit_channel_id, None);
+    }
+
+    #[test]
+    fn every_key_can_be_unset() {
+        let (db, mut settings) = guild();
+        for key in KEYS {
+            update(&db, &mut settings, key, Some(ChannelId::new(30)), "en-US").unwrap();
+            update(&db, &mut settings, key, None, "en-US").unwrap();
+        }
+
+        let saved = db.guild_settings(GuildId::new(1)).unwrap();
+        assert_eq!(saved.announcements_channel_id, None);
+        assert_eq!(saved.shuffle_category_id, None);
+        assert_eq!(saved.lobby_channel_id, None);
+        assert_eq!(saved.audit_channel_id, None);
+    }
+
+    #[test]
+    fn unknown_keys_are_refused() {
+        let (db, mut settings) = guild();
+
+        assert!(update(&db, &mut settings, "guild_id", None, "en-US").is_err());
+    }
+
+    #[test]
+    fn show_lists_settings_and_roles() {
+        let (db, mut settings) = guild();
+        update(
+            &db,
+            &mut settings,
+            "lobby_channel",
+            Some(ChannelId::new(32)),
+            "en-US",
+        )
+        .unwrap();
+        db.add_command_role(GuildId::new(1), "shuffle", RoleId::new(5))
+            .unwrap();
+
+        let settings = db.guild_settings(GuildId::new(1)).unwrap();
+        let command_roles = db.command_roles(GuildId::new(1)).unwrap();
+        let shown = show("en-US", &settings, &command_roles);
+
+        assert!(shown.contains("**lobby_channel**: <#32>"), "{shown}");
+        assert!(shown.contains("**audit_channel**: not set"), "{shown}");
+        assert!(shown.contains("`/shuffle`: <@&5>"), "{shown}");
+    }
+}
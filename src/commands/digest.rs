@@ -0,0 +1,93 @@
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
+use serenity::model::application::{CommandInteraction, CommandOptionType};
+use serenity::model::mention::Mention;
+use serenity::model::permissions::Permissions;
+use serenity::prelude::Context;
+
+use super::SlashCommand;
+use crate::digest::send_digest;
+use crate::discord::SerenityDiscord;
+use crate::i18n::{self, tr, tr_args};
+use crate::Handler;
+
+async fn run(
+    command: &CommandInteraction,
+    handler: &Handler,
+    ctx: &Context,
+) -> Result<String, String> {
+    let locale = command.locale.as_str();
+
+    let settings = super::guild_settings(command, handler)?;
+    let Some(announcements_channel_id) = settings.announcements_channel_id else {
+        return Err(tr(locale, "common.not_configured").to_string());
+    };
+
+    let subcommand_name = command
+        .data
+        .options
+        .first()
+        .expect("Expected subcommand")
+        .name
+        .as_str();
+
+    match subcommand_name {
+        "now" => {
+            match send_digest(
+                &SerenityDiscord::new(ctx),
+                &handler.db,
+                settings.guild_id,
+                announcements_channel_id,
+                command.user.id,
+                handler.theme.digest,
+                &handler.language,
+            )
+            .await
+            {
+                Ok(true) => Ok(tr_args(
+                    locale,
+                    "digest.sent",
+                    &[(
+                        "channel",
+                        &Mention::from(announcements_channel_id).to_string(),
+                    )],
+                )),
+                Ok(false) => Ok(tr(locale, "digest.empty").to_string()),
+                Err(e) => Err(e),
+            }
+        }
+        _ => Err(tr(locale, "common.invalid_subcommand").to_string()),
+    }
+}
+
+fn register() -> CreateCommand {
+    let now_subcommand = i18n::option(CommandOptionType::SubCommand, "now", "digest.now");
+
+    i18n::command("digest", "digest").add_option(now_subcommand)
+}
+
+pub struct Digest;
+
+#[async_trait]
+impl SlashCommand for Digest {
+    fn name(&self) -> &'static str {
+        "digest"
+    }
+
+    fn permissions(&self) -> Permissions {
+        Permissions::MANAGE_MESSAGES
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        command: &CommandInteraction,
+        handler: &Handler,
+        ctx: &Context,
+    ) -> Result<String, String> {
+        run(command, handler, ctx).await
+    }
+}
@@ -0,0 +1,90 @@
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
+use serenity::model::application::CommandInteraction;
+use serenity::model::mention::Mention;
+use serenity::model::permissions::Permissions;
+use serenity::model::prelude::UserId;
+use serenity::prelude::Context;
+
+use super::SlashCommand;
+use crate::db::{Record, DEFAULT_RATING};
+use crate::i18n::{self, tr, tr_args};
+use crate::Handler;
+
+/// Members listed, more would not fit in a message.
+const LIMIT: usize = 20;
+
+async fn run(command: &CommandInteraction, handler: &Handler) -> Result<String, String> {
+    let locale = command.locale.as_str();
+
+    let settings = super::guild_settings(command, handler)?;
+    let ratings = handler.db.ratings(settings.guild_id)?;
+    let records = handler.db.records(settings.guild_id)?;
+
+    let mut members: Vec<_> = ratings.keys().chain(records.keys()).copied().collect();
+    members.sort();
+    members.dedup();
+    if members.is_empty() {
+        return Ok(tr(locale, "leaderboard.empty").to_string());
+    }
+    let rating = |user_id: UserId| ratings.get(&user_id).copied().unwrap_or(DEFAULT_RATING);
+    members.sort_by(|a, b| rating(*b).total_cmp(&rating(*a)));
+
+    let mut lines = vec![format!("**{}**", tr(locale, "leaderboard.heading"))];
+    for (i, user_id) in members.iter().take(LIMIT).enumerate() {
+        let rank = (i + 1).to_string();
+        let member = Mention::from(*user_id).to_string();
+        let rating = format!("{:.0}", rating(*user_id));
+        let record = records.get(user_id).copied().unwrap_or_default();
+        lines.push(match record {
+            Record { games: 0, .. } => tr_args(
+                locale,
+                "leaderboard.entry_unplayed",
+                &[("rank", &rank), ("member", &member), ("rating", &rating)],
+            ),
+            Record { games, wins } => tr_args(
+                locale,
+                "leaderboard.entry",
+                &[
+                    ("rank", &rank),
+                    ("member", &member),
+                    ("rating", &rating),
+                    ("wins", &wins.to_string()),
+                    ("games", &games.to_string()),
+                    ("win_rate", &(wins * 100 / games).to_string()),
+                ],
+            ),
+        });
+    }
+    Ok(lines.join("\n"))
+}
+
+fn register() -> CreateCommand {
+    i18n::command("leaderboard", "leaderboard")
+}
+
+pub struct Leaderboard;
+
+#[async_trait]
+impl SlashCommand for Leaderboard {
+    fn name(&self) -> &'static str {
+        "leaderboard"
+    }
+
+    fn permissions(&self) -> Permissions {
+        Permissions::USE_APPLICATION_COMMANDS
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        command: &CommandInteraction,
+        handler: &Handler,
+        _ctx: &Context,
+    ) -> Result<String, String> {
+        run(command, handler).await
+    }
+}
@@ -1,2 +1,281 @@
 pub mod announce;
+pub mod config;
+pub mod digest;
+pub mod leaderboard;
+pub mod rating;
+pub mod regroup;
+pub mod result;
 pub mod shuffle;
+
+use serenity::async_trait;
+use serenity::builder::{AutocompleteChoice, CreateCommand};
+use serenity::model::application::{
+    CommandDataOption, CommandDataOptionValue, CommandInteraction, ComponentInteraction,
+};
+use serenity::model::permissions::Permissions;
+use serenity::prelude::Context;
+
+use crate::db::GuildSettings;
+use crate::i18n::tr;
+use crate::Handler;
+
+/// A slash command with everything needed to register it and handle its interactions.
+#[async_trait]
+pub trait SlashCommand: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Needed to use the command by members without an allowed role, see [`check_access`]. Also set
+    /// as the command's default member permissions.
+    fn permissions(&self) -> Permissions;
+
+    /// Needed to use the subcommand at `path`, like `["rating", "show"]`. The same as
+    /// [`permissions`](Self::permissions) unless a subcommand asks for less.
+    fn subcommand_permissions(&self, _path: &[&str]) -> Permissions {
+        self.permissions()
+    }
+
+    fn register(&self) -> CreateCommand;
+
+    /// Returns the reply to the user, an error when the command failed or was refused.
+    async fn run(
+        &self,
+        command: &CommandInteraction,
+        handler: &Handler,
+        ctx: &Context,
+    ) -> Result<String, String>;
+
+    /// Suggestions for the option the user is typing in.
+    async fn autocomplete(
+        &self,
+        _command: &CommandInteraction,
+        _handler: &Handler,
+        _ctx: &Context,
+    ) -> Vec<AutocompleteChoice> {
+        Vec::new()
+    }
+
+    /// Handles a button or select menu of this command, whose custom id starts with the command
+    /// name followed by a colon. Responding is up to the command, an error is sent back to the
+    /// user.
+    async fn component(
+        &self,
+        component: &ComponentInteraction,
+        _handler: &Handler,
+        _ctx: &Context,
+    ) -> Result<(), String> {
+        Err(tr(&component.locale, "common.not_implemented").to_string())
+    }
+}
+
+/// Every command of the bot.
+pub const COMMANDS: &[&dyn SlashCommand] = &[
+    &announce::Announce,
+    &shuffle::Shuffle,
+    &digest::Digest,
+    &config::Config,
+    &rating::Rating,
+    &result::MatchResult,
+    &leaderboard::Leaderboard,
+    &regroup::Regroup,
+];
+
+pub fn find(name: &str) -> Option<&'static dyn SlashCommand> {
+    COMMANDS
+        .iter()
+        .find(|command| command.name() == name)
+        .copied()
+}
+
+/// Commands and subcommands roles can be allowed to use, see [`check_access`].
+pub const PERMISSION_SCOPES: &[&str] = &[
+    "announce",
+    "announce anilist",
+    "announce tmdb",
+    "config",
+    "digest",
+    "leaderboard",
+    "rating",
+    "regroup",
+    "result",
+    "shuffle",
+];
+
+/// Loads the settings of the guild a command was used in, or the reply to send when there are
+/// none.
+pub fn guild_settings(
+    command: &CommandInteraction,
+    handler: &Handler,
+) -> Result<GuildSettings, String> {
+    match command.guild_id {
+        Some(guild_id) => handler.db.guild_settings(guild_id),
+        None => Err(tr(&command.locale, "common.guild_only").to_string()),
+    }
+}
+
+/// The command name followed by the names of the invoked subcommand group and subcommand.
+pub fn command_path(command: &CommandInteraction) -> Vec<&str> {
+    let mut path = vec![command.data.name.as_str()];
+    let mut options: &[CommandDataOption] = &command.data.options;
+    while let Some(option) = options.first() {
+        match &option.value {
+            CommandDataOptionValue::SubCommandGroup(sub_options)
+            | CommandDataOptionValue::SubCommand(sub_options) => {
+                path.push(option.name.as_str());
+                options = sub_options;
+            }
+            _ => break,
+        }
+    }
+    path
+}
+
+/// Checks whether the invoking member may use the command, or returns the reply to send.
+///
+/// The bot admin and server administrators may use everything. Other members need the command's
+/// `permissions`, or one of the roles allowed for the command or the invoked subcommand. Discord
+/// only shows a command to members with its default member permissions, server administrators can
+/// show it to the allowed roles as well in the integration settings.
+pub fn check_access(
+    command: &CommandInteraction,
+    handler: &Handler,
+    permissions: Permissions,
+) -> Result<(), String> {
+    let denied = || tr(&command.locale, "common.denied").to_string();
+
+    if command.user.id == handler.admin_user_id {
+        return Ok(());
+    }
+    let (Some(guild_id), Some(member)) = (command.guild_id, &command.member) else {
+        return Err(denied());
+    };
+    let member_permissions = member.permissions.unwrap_or_default();
+    if member_permissions.administrator() || member_permissions.contains(permissions) {
+        return Ok(());
+    }
+
+    let command_roles = handler.db.command_roles(guild_id)?;
+    let path = command_path(command);
+    let scopes: Vec<String> = (1..=path.len()).map(|len| path[..len].join(" ")).collect();
+    let allowed = command_roles
+        .iter()
+        .any(|(scope, role_id)| scopes.contains(scope) && member.roles.contains(role_id));
+    if allowed {
+        Ok(())
+    } else {
+        Err(denied())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use serenity::model::id::{GuildId, RoleId};
+
+    use super::*;
+
+    const MEMBER: u64 = 5;
+    const ROLE: u64 = 7;
+
+    /// `/rating <subcommand>` used by a member with `permissions` and `roles`.
+    fn rating(
+        user_id: u64,
+        subcommand: &str,
+        permissions: Permissions,
+        roles: &[u64],
+    ) -> CommandInteraction {
+        let roles: Vec<String> = roles.iter().map(u64::to_string).collect();
+        serde_json::from_value(json!({
+            "id": "1",
+            "application_id": "2",
+            "type": 2,
+            "token": "token",
+            "version": 1,
+            "locale": "en-US",
+            "guild_id": "1",
+            "channel_id": "3",
+            "entitlements": [],
+            "attachment_size_limit": 8388608,
+            "authorizing_integration_owners": {},
+            "data": {
+                "id": "4",
+                "name": "rating",
+                "type": 1,
+                "options": [{
+                    "name": subcommand,
+                    "type": 1,
+                    "options": [{ "name": "member", "type": 6, "value": "8" }]
+                }]
+            },
+            "member": {
+                "user": { "id": user_id.to_string(), "username": "member", "discriminator": "0" },
+                "roles": roles,
+                "joined_at": "2024-01-01T00:00:00Z",
+                "deaf": false,
+                "mute": false,
+                "flags": 0,
+                "permissions": permissions.bits().to_string()
+            }
+        }))
+        .unwrap()
+    }
+
+    fn access(command: &CommandInteraction, handler: &Handler) -> Result<(), String> {
+        let permissions = rating::Rating.subcommand_permissions(&command_path(command));
+        check_access(command, handler, permissions)
+    }
+
+    #[test]
+    fn paths_include_the_subcommand() {
+        let command = rating(MEMBER, "show", Permissions::empty(), &[]);
+
+        assert_eq!(command_path(&command), vec!["rating", "show"]);
+    }
+
+    #[test]
+    fn the_admin_may_use_everything() {
+        let handler = Handler::for_tests("http://localhost");
+        let command = rating(99, "set", Permissions::empty(), &[]);
+
+        assert_eq!(access(&command, &handler), Ok(()));
+    }
+
+    #[test]
+    fn subcommands_can_ask_for_less() {
+        let handler = Handler::for_tests("http://localhost");
+        let permissions = Permissions::USE_APPLICATION_COMMANDS;
+
+        assert_eq!(
+            access(&rating(MEMBER, "show", permissions, &[]), &handler),
+            Ok(())
+        );
+        assert_eq!(
+            access(&rating(MEMBER, "set", permissions, &[]), &handler),
+            Err("You are not allowed to use this command".to_string())
+        );
+        assert_eq!(
+            access(
+                &rating(MEMBER, "set", Permissions::MOVE_MEMBERS, &[]),
+                &handler
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn allowed_roles_add_access() {
+        let handler = Handler::for_tests("http://localhost");
+        handler
+            .db
+            .add_command_role(GuildId::new(1), "rating", RoleId::new(ROLE))
+            .unwrap();
+
+        assert_eq!(
+            access(
+                &rating(MEMBER, "set", Permissions::empty(), &[ROLE]),
+                &handler
+            ),
+            Ok(())
+        );
+        assert!(access(&rating(MEMBER, "set", Permissions::empty(), &[8]), &handler).is_err());
+    }
+}
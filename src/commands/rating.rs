@@ -0,0 +1,123 @@
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
+use serenity::model::application::{
+    CommandInteraction, CommandOptionType, ResolvedOption, ResolvedValue,
+};
+use serenity::model::mention::Mention;
+use serenity::model::permissions::Permissions;
+use serenity::prelude::Context;
+
+use super::SlashCommand;
+use crate::db::DEFAULT_RATING;
+use crate::i18n::{self, tr, tr_args};
+use crate::Handler;
+
+/// Highest rating that can be set by hand.
+const MAX_RATING: i64 = 3000;
+
+async fn run(command: &CommandInteraction, handler: &Handler) -> Result<String, String> {
+    let options: &[ResolvedOption] = &command.data.options();
+    let locale = command.locale.as_str();
+
+    let settings = super::guild_settings(command, handler)?;
+
+    let Some(ResolvedOption {
+        name: subcommand,
+        value: ResolvedValue::SubCommand(options),
+        ..
+    }) = options.first()
+    else {
+        return Err(tr(locale, "common.invalid_subcommand").to_string());
+    };
+    let (mut member, mut value) = (None, None);
+    for option in options {
+        match (option.name, &option.value) {
+            ("member", ResolvedValue::User(user, _)) => member = Some(user.id),
+            ("value", ResolvedValue::Integer(rating)) => value = Some(*rating),
+            _ => {}
+        }
+    }
+    let Some(user_id) = member else {
+        return Err(tr(locale, "common.invalid_id").to_string());
+    };
+    let mention = Mention::from(user_id).to_string();
+
+    match *subcommand {
+        "show" => {
+            let ratings = handler.db.ratings(settings.guild_id)?;
+            let rating = ratings.get(&user_id).copied().unwrap_or(DEFAULT_RATING);
+            Ok(tr_args(
+                locale,
+                "rating.current",
+                &[("member", &mention), ("rating", &format!("{rating:.0}"))],
+            ))
+        }
+        "set" => {
+            let Some(rating) = value else {
+                return Err(tr(locale, "common.invalid_number").to_string());
+            };
+            handler
+                .db
+                .set_rating(settings.guild_id, user_id, rating as f64)?;
+            Ok(tr_args(
+                locale,
+                "rating.updated",
+                &[("member", &mention), ("rating", &rating.to_string())],
+            ))
+        }
+        _ => Err(tr(locale, "common.invalid_subcommand").to_string()),
+    }
+}
+
+fn register() -> CreateCommand {
+    let member_option =
+        || i18n::option(CommandOptionType::User, "member", "rating.member").required(true);
+    let show_subcommand = i18n::option(CommandOptionType::SubCommand, "show", "rating.show")
+        .add_sub_option(member_option());
+    let set_subcommand = i18n::option(CommandOptionType::SubCommand, "set", "rating.set")
+        .add_sub_option(member_option())
+        .add_sub_option(
+            i18n::option(CommandOptionType::Integer, "value", "rating.value")
+                .min_int_value(0)
+                .max_int_value(MAX_RATING as u64)
+                .required(true),
+        );
+
+    i18n::command("rating", "rating")
+        .add_option(show_subcommand)
+        .add_option(set_subcommand)
+}
+
+pub struct Rating;
+
+#[async_trait]
+impl SlashCommand for Rating {
+    fn name(&self) -> &'static str {
+        "rating"
+    }
+
+    fn permissions(&self) -> Permissions {
+        Permissions::MOVE_MEMBERS
+    }
+
+    fn subcommand_permissions(&self, path: &[&str]) -> Permissions {
+        match path {
+            // Ratings are visible on the leaderboard anyway
+            ["rating", "show"] => Permissions::USE_APPLICATION_COMMANDS,
+            _ => self.permissions(),
+        }
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        command: &CommandInteraction,
+        handler: &Handler,
+        _ctx: &Context,
+    ) -> Result<String, String> {
+        run(command, handler).await
+    }
+}
@@ -0,0 +1,388 @@
+use std::time::Duration;
+
+use serenity::async_trait;
+use serenity::builder::{CreateCommand, CreateEmbed};
+use serenity::model::application::CommandInteraction;
+use serenity::model::mention::Mention;
+use serenity::model::permissions::Permissions;
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+use serenity::prelude::Context;
+use tracing::{error, info};
+
+use super::{shuffle, SlashCommand};
+use crate::audit::{self, Outcome};
+use crate::db::Database;
+use crate::discord::{DiscordOps, SerenityDiscord};
+use crate::i18n::{self, tr, tr_args};
+use crate::metrics::METRICS;
+use crate::Handler;
+
+async fn run(
+    command: &CommandInteraction,
+    handler: &Handler,
+    ctx: &Context,
+) -> Result<String, String> {
+    let locale = command.locale.as_str();
+
+    let settings = super::guild_settings(command, handler)?;
+    let (Some(shuffle_category_id), Some(lobby_channel_id)) =
+        (settings.shuffle_category_id, settings.lobby_channel_id)
+    else {
+        return Err(tr(locale, "common.not_configured").to_string());
+    };
+
+    let discord = SerenityDiscord::new(ctx);
+    let mut audit_entries = Vec::new();
+    let regrouped = regroup(
+        &discord,
+        &handler.db,
+        command.user.id,
+        settings.guild_id,
+        (shuffle_category_id, lobby_channel_id),
+        &mut audit_entries,
+    )
+    .await;
+    audit::log(&discord, &handler.db, settings.guild_id, audit_entries).await;
+
+    let Some(regrouped) = regrouped? else {
+        return Ok(tr(locale, "regroup.nothing").to_string());
+    };
+    let mut reply = tr_args(
+        locale,
+        "regroup.done",
+        &[
+            ("members", &regrouped.moved.to_string()),
+            ("lobby", &Mention::from(lobby_channel_id).to_string()),
+        ],
+    );
+    if !regrouped.not_moved.is_empty() {
+        let members: Vec<String> = regrouped
+            .not_moved
+            .iter()
+            .map(|user_id| Mention::from(*user_id).to_string())
+            .collect();
+        reply.push('\n');
+        reply.push_str(&tr_args(
+            locale,
+            "regroup.not_moved",
+            &[("members", &members.join(", "))],
+        ));
+    }
+    if !regrouped.not_deleted.is_empty() {
+        let channels: Vec<String> = regrouped
+            .not_deleted
+            .iter()
+            .map(|channel_id| Mention::from(*channel_id).to_string())
+            .collect();
+        reply.push('\n');
+        reply.push_str(&tr_args(
+            locale,
+            "regroup.not_deleted",
+            &[("channels", &channels.join(", "))],
+        ));
+    }
+    Ok(reply)
+}
+
+/// How a regroup went.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Regrouped {
+    /// Number of members moved back to the lobby
+    pub moved: usize,
+    /// Members that could not be moved, their channels were kept
+    pub not_moved: Vec<UserId>,
+    /// Team channels everyone was moved out of that could not be deleted
+    pub not_deleted: Vec<ChannelId>,
+}
+
+/// Moves everyone in a team channel back to the lobby and deletes the team channels everyone was
+/// moved out of, deleting the others would disconnect whoever is left. Returns `None` when there
+/// were no team channels.
+pub async fn regroup(
+    discord: &dyn DiscordOps,
+    db: &Database,
+    user_id: UserId,
+    guild_id: GuildId,
+    (shuffle_category_id, lobby_channel_id): (ChannelId, ChannelId),
+    audit_entries: &mut Vec<CreateEmbed>,
+) -> Result<Option<Regrouped>, String> {
+    let team_channels: Vec<_> = discord
+        .channels(guild_id)
+        .await?
+        .into_iter()
+        .filter(|channel| {
+            channel.parent_id == Some(shuffle_category_id) && channel.id != lobby_channel_id
+        })
+        .collect();
+    if team_channels.is_empty() {
+        return Ok(None);
+    }
+
+    let mut regrouped = Regrouped {
+        moved: 0,
+        not_moved: Vec::new(),
+        not_deleted: Vec::new(),
+    };
+    for channel in team_channels {
+        let mut all_moved = true;
+        for member in discord.voice_members(guild_id, channel.id).await? {
+            let action = format!("Moved {} back to the lobby", member.name);
+            let outcome = match discord
+                .move_member(guild_id, member.user_id, lobby_channel_id)
+                .await
+            {
+                Ok(()) => {
+                    METRICS.members_moved.inc();
+                    regrouped.moved += 1;
+                    Outcome::Success(Mention::from(member.user_id).to_string())
+                }
+                Err(e) => {
+                    all_moved = false;
+                    regrouped.not_moved.push(member.user_id);
+                    Outcome::Failure(e)
+                }
+            };
+            audit_entries.push(audit::entry(user_id, &action, outcome));
+        }
+
+        if !all_moved {
+            audit_entries.push(audit::entry(
+                user_id,
+                &format!("Kept channel {}", channel.name),
+                Outcome::Failure("Not everyone could be moved out".to_string()),
+            ));
+            continue;
+        }
+        let action = format!("Deleted channel {}", channel.name);
+        match discord.delete_channel(channel.id).await {
+            Ok(()) => {
+                shuffle::forget_team_channel(db, channel.id);
+                audit_entries.push(audit::entry(
+                    user_id,
+                    &action,
+                    Outcome::Success(String::new()),
+                ))
+            }
+            Err(e) => {
+                // The members were moved already, the other channels can still be cleaned up
+                regrouped.not_deleted.push(channel.id);
+                audit_entries.push(audit::entry(user_id, &action, Outcome::Failure(e)));
+            }
+        }
+    }
+    Ok(Some(regrouped))
+}
+
+/// Regroups after `delay`, unless there was another shuffle in the meantime.
+pub async fn regroup_later(
+    discord: SerenityDiscord,
+    db: Database,
+    user_id: UserId,
+    guild_id: GuildId,
+    shuffle_id: i64,
+    delay: Duration,
+) {
+    tokio::time::sleep(delay).await;
+
+    match db.last_shuffle(guild_id) {
+        Ok(Some(shuffle)) if shuffle.id == shuffle_id => {}
+        Ok(_) => {
+            info!("Skipping regroup, the teams were shuffled again");
+            return;
+        }
+        Err(e) => {
+            error!(error = %e, "Cannot look up last shuffle");
+            return;
+        }
+    }
+    // The channels may have been reconfigured while waiting
+    let settings = match db.guild_settings(guild_id) {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!(error = %e, "Cannot read guild settings");
+            return;
+        }
+    };
+    let (Some(shuffle_category_id), Some(lobby_channel_id)) =
+        (settings.shuffle_category_id, settings.lobby_channel_id)
+    else {
+        return;
+    };
+
+    let mut audit_entries = Vec::new();
+    match regroup(
+        &discord,
+        &db,
+        user_id,
+        guild_id,
+        (shuffle_category_id, lobby_channel_id),
+        &mut audit_entries,
+    )
+    .await
+    {
+        Ok(Some(regrouped)) => info!(
+            moved = regrouped.moved,
+            not_moved = regrouped.not_moved.len(),
+            not_deleted = regrouped.not_deleted.len(),
+            "Regrouped automatically"
+        ),
+        Ok(None) => info!("Nothing to regroup"),
+        Err(e) => error!(error = %e, "Cannot regroup automatically"),
+    }
+    audit::log(&discord, &db, guild_id, audit_entries).await;
+}
+
+fn register() -> CreateCommand {
+    i18n::command("regroup", "regroup")
+}
+
+pub struct Regroup;
+
+#[async_trait]
+impl SlashCommand for Regroup {
+    fn name(&self) -> &'static str {
+        "regroup"
+    }
+
+    fn permissions(&self) -> Permissions {
+        Permissions::MOVE_MEMBERS | Permissions::MANAGE_CHANNELS
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        command: &CommandInteraction,
+        handler: &Handler,
+        ctx: &Context,
+    ) -> Result<String, String> {
+        run(command, handler, ctx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discord::fake::FakeDiscord;
+
+    const CATEGORY: u64 = 10;
+    const LOBBY: u64 = 11;
+    const TEAM_1: u64 = 12;
+    const TEAM_2: u64 = 13;
+    const ELSEWHERE: u64 = 20;
+
+    async fn run_regroup(discord: &FakeDiscord) -> Result<Option<Regrouped>, String> {
+        regroup(
+            discord,
+            &Database::open(":memory:").unwrap(),
+            UserId::new(99),
+            GuildId::new(1),
+            (ChannelId::new(CATEGORY), ChannelId::new(LOBBY)),
+            &mut Vec::new(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn brings_everyone_back_to_the_lobby() {
+        let discord = FakeDiscord::default();
+        discord.add_channel(LOBBY, "lobby", Some(CATEGORY));
+        discord.add_channel(TEAM_1, "team 1", Some(CATEGORY));
+        discord.add_channel(TEAM_2, "team 2", Some(CATEGORY));
+        discord.add_channel(ELSEWHERE, "general", None);
+        discord.connect(1, "member 1", TEAM_1);
+        discord.connect(2, "member 2", TEAM_2);
+        discord.connect(3, "member 3", TEAM_2);
+        discord.connect(4, "member 4", ELSEWHERE);
+
+        assert_eq!(
+            run_regroup(&discord).await,
+            Ok(Some(Regrouped {
+                moved: 3,
+                not_moved: Vec::new(),
+                not_deleted: Vec::new()
+            }))
+        );
+
+        let mut in_lobby = discord.members_in(ChannelId::new(LOBBY));
+        in_lobby.sort();
+        assert_eq!(
+            in_lobby,
+            vec![UserId::new(1), UserId::new(2), UserId::new(3)]
+        );
+        assert_eq!(
+            discord.members_in(ChannelId::new(ELSEWHERE)),
+            vec![UserId::new(4)]
+        );
+        assert_eq!(
+            discord.state.lock().unwrap().deleted_channels,
+            vec![ChannelId::new(TEAM_1), ChannelId::new(TEAM_2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_channels_with_members_that_could_not_be_moved() {
+        let discord = FakeDiscord::default();
+        discord.add_channel(LOBBY, "lobby", Some(CATEGORY));
+        discord.add_channel(TEAM_1, "team 1", Some(CATEGORY));
+        discord.add_channel(TEAM_2, "team 2", Some(CATEGORY));
+        discord.connect(1, "member 1", TEAM_1);
+        discord.connect(2, "member 2", TEAM_2);
+        discord.connect(3, "member 3", TEAM_2);
+        discord.state.lock().unwrap().unmovable = vec![UserId::new(3)];
+
+        assert_eq!(
+            run_regroup(&discord).await,
+            Ok(Some(Regrouped {
+                moved: 2,
+                not_moved: vec![UserId::new(3)],
+                not_deleted: Vec::new()
+            }))
+        );
+        assert_eq!(
+            discord.state.lock().unwrap().deleted_channels,
+            vec![ChannelId::new(TEAM_1)]
+        );
+        assert_eq!(
+            discord.members_in(ChannelId::new(TEAM_2)),
+            vec![UserId::new(3)]
+        );
+    }
+
+    #[tokio::test]
+    async fn carries_on_when_a_channel_cannot_be_deleted() {
+        let discord = FakeDiscord::default();
+        discord.add_channel(LOBBY, "lobby", Some(CATEGORY));
+        discord.add_channel(TEAM_1, "team 1", Some(CATEGORY));
+        discord.add_channel(TEAM_2, "team 2", Some(CATEGORY));
+        discord.connect(1, "member 1", TEAM_1);
+        discord.connect(2, "member 2", TEAM_2);
+        discord.state.lock().unwrap().undeletable = vec![ChannelId::new(TEAM_1)];
+
+        assert_eq!(
+            run_regroup(&discord).await,
+            Ok(Some(Regrouped {
+                moved: 2,
+                not_moved: Vec::new(),
+                not_deleted: vec![ChannelId::new(TEAM_1)]
+            }))
+        );
+        assert_eq!(
+            discord.state.lock().unwrap().deleted_channels,
+            vec![ChannelId::new(TEAM_2)]
+        );
+        assert_eq!(discord.members_in(ChannelId::new(LOBBY)).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn does_nothing_without_team_channels() {
+        let discord = FakeDiscord::default();
+        discord.add_channel(LOBBY, "lobby", Some(CATEGORY));
+        discord.connect(1, "member 1", LOBBY);
+
+        assert_eq!(run_regroup(&discord).await, Ok(None));
+        assert!(discord.state.lock().unwrap().moves.is_empty());
+    }
+}
@@ -0,0 +1,111 @@
+use serenity::async_trait;
+use serenity::builder::CreateCommand;
+use serenity::model::application::{CommandInteraction, CommandOptionType, ResolvedValue};
+use serenity::model::mention::Mention;
+use serenity::model::permissions::Permissions;
+use serenity::prelude::Context;
+
+use super::SlashCommand;
+use crate::db::DEFAULT_RATING;
+use crate::elo;
+use crate::i18n::{self, tr, tr_args};
+use crate::Handler;
+
+async fn run(command: &CommandInteraction, handler: &Handler) -> Result<String, String> {
+    let locale = command.locale.as_str();
+
+    let settings = super::guild_settings(command, handler)?;
+    let Some(ResolvedValue::Integer(team)) = command
+        .data
+        .options()
+        .into_iter()
+        .find(|option| option.name == "winner")
+        .map(|option| option.value)
+    else {
+        return Err(tr(locale, "common.invalid_number").to_string());
+    };
+
+    let Some(shuffle) = handler.db.last_shuffle(settings.guild_id)? else {
+        return Err(tr(locale, "result.no_shuffle").to_string());
+    };
+    if shuffle.winner.is_some() {
+        return Err(tr(locale, "result.already_recorded").to_string());
+    }
+    // Teams are numbered like their channels, starting at 1
+    let winner = team as usize - 1;
+    if shuffle.teams.get(winner).is_none_or(Vec::is_empty) {
+        return Err(tr_args(
+            locale,
+            "result.invalid_team",
+            &[("team", &team.to_string())],
+        ));
+    }
+
+    let mut ratings = handler.db.ratings(settings.guild_id)?;
+    let before = ratings.clone();
+    elo::update(&mut ratings, &shuffle.teams, winner);
+    ratings.retain(|user_id, _| {
+        shuffle
+            .teams
+            .iter()
+            .any(|members| members.contains(user_id))
+    });
+    // Checked again when storing, another result may have been recorded in the meantime
+    if !handler
+        .db
+        .record_result(settings.guild_id, shuffle.id, winner, &ratings)?
+    {
+        return Err(tr(locale, "result.already_recorded").to_string());
+    }
+
+    let mut lines = vec![tr_args(
+        locale,
+        "result.recorded",
+        &[("team", &team.to_string())],
+    )];
+    for user_id in shuffle.teams.iter().flatten() {
+        let old = before.get(user_id).copied().unwrap_or(DEFAULT_RATING);
+        let new = ratings[user_id];
+        lines.push(format!(
+            "{}: {old:.0} → {new:.0} ({:+.0})",
+            Mention::from(*user_id),
+            new - old
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+fn register() -> CreateCommand {
+    let winner_option = i18n::option(CommandOptionType::Integer, "winner", "result.winner")
+        .min_int_value(1)
+        .max_int_value(10)
+        .required(true);
+
+    i18n::command("result", "result").add_option(winner_option)
+}
+
+pub struct MatchResult;
+
+#[async_trait]
+impl SlashCommand for MatchResult {
+    fn name(&self) -> &'static str {
+        "result"
+    }
+
+    fn permissions(&self) -> Permissions {
+        Permissions::MOVE_MEMBERS
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        command: &CommandInteraction,
+        handler: &Handler,
+        _ctx: &Context,
+    ) -> Result<String, String> {
+        run(command, handler).await
+    }
+}
@@ -1,92 +1,1135 @@
-extern crate rand;
+mod draft;
+mod teams;
 
-use rand::seq::SliceRandom;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Utc;
 use rand::rng;
+use rand::seq::SliceRandom;
 
 use serenity::{
-    builder::{CreateChannel, CreateCommand, CreateCommandOption},
-    model::application::{CommandDataOptionValue, CommandInteraction, CommandOptionType},
-    model::prelude::{ChannelType, GuildChannel},
+    async_trait,
+    builder::{
+        CreateActionRow, CreateCommand, CreateEmbed, CreateInteractionResponse,
+        CreateInteractionResponseFollowup, CreateInteractionResponseMessage, CreateMessage,
+        CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption,
+    },
+    model::application::{
+        CommandInteraction, CommandOptionType, ComponentInteraction, ComponentInteractionDataKind,
+        ResolvedOption, ResolvedValue,
+    },
+    model::mention::Mention,
+    model::permissions::Permissions,
+    model::prelude::{ChannelId, GuildId, UserId},
     prelude::Context,
 };
+use tracing::{error, Instrument};
 
+use super::{regroup, SlashCommand};
+use crate::audit::{self, Outcome};
+use crate::db::{Database, GuildSettings, DEFAULT_RATING};
+use crate::discord::{DiscordOps, SerenityDiscord, VoiceMember};
+use crate::i18n::{self, tr, tr_args};
+use crate::metrics::METRICS;
 use crate::Handler;
+use draft::Draft;
+use teams::{Layout, LayoutError, Teammates};
 
-pub async fn run(command: &CommandInteraction, handler: &Handler, ctx: &Context) -> String {
-    let options = &command.data.options;
+/// Longest wait in minutes before regrouping automatically.
+const MAX_AUTO_REGROUP_AFTER: u64 = 240;
 
-    let n_teams_option = options.get(0).expect("Expected subcommand");
+/// Most options a select menu can have.
+const MAX_SELECT_OPTIONS: usize = 25;
+
+async fn run(
+    command: &CommandInteraction,
+    handler: &Handler,
+    ctx: &Context,
+) -> Result<String, String> {
+    let options: &[ResolvedOption] = &command.data.options();
+    let locale = command.locale.as_str();
+
+    let mut layouts = Vec::new();
+    let mut mode = "random";
+    let mut captains = Vec::new();
+    let mut auto_regroup_after = None;
+    for option in options {
+        match (option.name, &option.value) {
+            ("n_teams", ResolvedValue::Integer(value)) => {
+                layouts.push(Layout::Teams(*value as usize))
+            }
+            ("team_size", ResolvedValue::Integer(value)) => {
+                layouts.push(Layout::TeamSize(*value as usize))
+            }
+            ("format", ResolvedValue::String(value)) => match teams::parse_format(value) {
+                Some(sizes) => layouts.push(Layout::Format(sizes)),
+                None => {
+                    return Err(tr_args(
+                        locale,
+                        "shuffle.invalid_format",
+                        &[("format", value)],
+                    ))
+                }
+            },
+            ("mode", ResolvedValue::String(value)) => mode = value,
+            ("captains", ResolvedValue::String(value)) => match parse_mentions(value) {
+                Some(user_ids) => captains = user_ids,
+                None => return Err(tr(locale, "shuffle.invalid_captains").to_string()),
+            },
+            ("auto_regroup_after", ResolvedValue::Integer(value)) => {
+                auto_regroup_after = Some(*value as u64)
+            }
+            _ => {}
+        }
+    }
+    let Ok([layout]) = <[Layout; 1]>::try_from(layouts) else {
+        return Err(tr(locale, "shuffle.one_layout").to_string());
+    };
+    if !captains.is_empty() && mode != "captains" {
+        return Err(tr(locale, "shuffle.captains_without_draft").to_string());
+    }
 
-    let n_teams = if let CommandDataOptionValue::Integer(n_teams) = n_teams_option.value {
-        n_teams
+    let settings = super::guild_settings(command, handler)?;
+    let (Some(shuffle_category_id), Some(lobby_channel_id)) =
+        (settings.shuffle_category_id, settings.lobby_channel_id)
+    else {
+        return Err(tr(locale, "common.not_configured").to_string());
+    };
+    let discord = SerenityDiscord::new(ctx);
+    let mut audit_entries = Vec::new();
+    // Added to the reply below the teams
+    let mut notes = Vec::new();
+    let teams = if mode == "captains" {
+        let (members, sizes) = lobby_teams(
+            &discord,
+            locale,
+            settings.guild_id,
+            lobby_channel_id,
+            &layout,
+        )
+        .await?;
+        let (captains, members) = pick_captains(locale, members, &captains, sizes.len())?;
+        let draft = ActiveDraft {
+            id: command.id.get(),
+            locale: locale.to_string(),
+            user_id: command.user.id,
+            channels: (shuffle_category_id, lobby_channel_id),
+            auto_regroup_after,
+            draft: Draft::new(captains, members, &sizes)?,
+        };
+        match start_draft(
+            &discord,
+            &handler.db,
+            &handler.drafts,
+            settings.guild_id,
+            command.channel_id,
+            draft,
+            &mut audit_entries,
+        )
+        .await
+        {
+            Ok((DraftStart::Posted(link), cancelled)) => {
+                let mut reply = tr_args(locale, "shuffle.draft.started", &[("link", &link)]);
+                if cancelled {
+                    reply.push('\n');
+                    reply.push_str(tr(locale, "shuffle.draft.cancelled"));
+                }
+                return Ok(reply);
+            }
+            Ok((DraftStart::Complete(teams), cancelled)) => {
+                if cancelled {
+                    notes.push(tr(locale, "shuffle.draft.cancelled").to_string());
+                }
+                Ok(teams)
+            }
+            Err(e) => Err(e),
+        }
     } else {
-        return "Please provide a valid ID".to_string();
+        let mode = match mode {
+            "balanced" => Mode::Balanced(handler.db.ratings(settings.guild_id)?),
+            _ => Mode::Random,
+        };
+        // Only makes the teams more varied, not worth failing the shuffle for
+        let recent_teams = handler
+            .db
+            .recent_teams(settings.guild_id, handler.shuffle_history)
+            .unwrap_or_else(|e| {
+                error!(error = %e, "Cannot read recent teams");
+                Vec::new()
+            });
+
+        shuffle(
+            &discord,
+            &handler.db,
+            locale,
+            command.user.id,
+            &settings,
+            (shuffle_category_id, lobby_channel_id),
+            &layout,
+            mode,
+            &Teammates::new(&recent_teams),
+            &mut audit_entries,
+        )
+        .await
+    };
+    // Whatever happened before a failure still needs to be accounted for
+    audit::log(&discord, &handler.db, settings.guild_id, audit_entries).await;
+
+    let teams = teams?;
+    let mut reply = tr_args(
+        locale,
+        "shuffle.shuffling",
+        &[("n_teams", &teams.len().to_string())],
+    );
+    notes.extend(finish(
+        &discord,
+        &handler.db,
+        locale,
+        command.user.id,
+        settings.guild_id,
+        &teams,
+        auto_regroup_after,
+    ));
+    for note in notes {
+        reply.push('\n');
+        reply.push_str(&note);
+    }
+    Ok(reply)
+}
+
+/// Records the teams for /result and schedules the automatic regroup, returns a note about the
+/// regroup for the reply if there is one.
+fn finish(
+    discord: &SerenityDiscord,
+    db: &Database,
+    locale: &str,
+    user_id: UserId,
+    guild_id: GuildId,
+    teams: &[Vec<UserId>],
+    auto_regroup_after: Option<u64>,
+) -> Option<String> {
+    // Needed by /result, losing it only costs the ratings of this game
+    let shuffle_id = match db.record_shuffle(guild_id, teams, Utc::now().timestamp()) {
+        Ok(shuffle_id) => shuffle_id,
+        Err(e) => {
+            error!(error = %e, "Cannot record shuffle");
+            // Without a recorded shuffle there is no telling whether the teams were shuffled
+            // again, so no automatic regroup either
+            return None;
+        }
     };
+    let minutes = auto_regroup_after?;
+    tokio::spawn(
+        regroup::regroup_later(
+            discord.clone(),
+            db.clone(),
+            user_id,
+            guild_id,
+            shuffle_id,
+            Duration::from_secs(minutes * 60),
+        )
+        .in_current_span(),
+    );
+    Some(tr_args(
+        locale,
+        "shuffle.auto_regroup",
+        &[("minutes", &minutes.to_string())],
+    ))
+}
 
-    let channels = handler.guild_id.channels(&ctx.http).await.unwrap();
+/// How lobby members are split into teams.
+enum Mode {
+    Random,
+    /// Teams of equal strength according to these ratings
+    Balanced(HashMap<UserId, f64>),
+}
 
-    let mut members_in_lobby = vec![];
+/// Parses mentions of users separated by spaces or commas.
+fn parse_mentions(value: &str) -> Option<Vec<UserId>> {
+    let user_ids = value
+        .split([' ', ','])
+        .filter(|mention| !mention.is_empty())
+        .map(|mention| {
+            let id = mention.strip_prefix("<@")?.strip_suffix('>')?;
+            let id: u64 = id.strip_prefix('!').unwrap_or(id).parse().ok()?;
+            (id != 0).then(|| UserId::new(id))
+        })
+        .collect::<Option<Vec<UserId>>>()?;
+    (!user_ids.is_empty()).then_some(user_ids)
+}
 
-    for channel in channels {
-        if channel.1.parent_id.is_some() {
-            if channel.1.parent_id.unwrap() == handler.shuffle_category_id {
-                if channel.0 != handler.lobby_channel_id {
-                    match channel.1.delete(&ctx.http).await {
-                        Ok(_r) => {}
-                        Err(e) => return e.to_string(),
-                    }
-                } else {
-                    match channel.1.members(&ctx.cache) {
-                        Ok(members) => members_in_lobby = members,
-                        Err(e) => return e.to_string(),
-                    }
-                }
-            }
+/// Splits the lobby into captains and the members they pick from, either the `chosen` captains or
+/// random ones.
+fn pick_captains(
+    locale: &str,
+    mut members: Vec<VoiceMember>,
+    chosen: &[UserId],
+    n_teams: usize,
+) -> Result<(Vec<VoiceMember>, Vec<VoiceMember>), String> {
+    if chosen.is_empty() {
+        members.shuffle(&mut rng());
+        let rest = members.split_off(n_teams);
+        return Ok((members, rest));
+    }
+    if chosen.len() != n_teams {
+        return Err(tr_args(
+            locale,
+            "shuffle.captain_count",
+            &[("n_teams", &n_teams.to_string())],
+        ));
+    }
+
+    let mut captains = Vec::new();
+    for user_id in chosen {
+        let Some(index) = members.iter().position(|member| member.user_id == *user_id) else {
+            return Err(tr_args(
+                locale,
+                "shuffle.captain_not_in_lobby",
+                &[("captain", &Mention::from(*user_id).to_string())],
+            ));
+        };
+        captains.push(members.remove(index));
+    }
+    Ok((captains, members))
+}
+
+/// Drafts in progress, at most one per guild.
+#[derive(Default)]
+pub struct Drafts {
+    drafts: Mutex<HashMap<GuildId, ActiveDraft>>,
+}
+
+struct ActiveDraft {
+    /// Id of the /shuffle interaction, so picks in the message of an older draft are refused
+    id: u64,
+    locale: String,
+    /// Who started the draft, the channels are created on their behalf
+    user_id: UserId,
+    /// The shuffle category and the lobby
+    channels: (ChannelId, ChannelId),
+    auto_regroup_after: Option<u64>,
+    draft: Draft<VoiceMember>,
+}
+
+enum DraftStart {
+    /// Link to the message the captains pick in
+    Posted(String),
+    /// There was nothing to pick, these teams were moved right away
+    Complete(Vec<Vec<UserId>>),
+}
+
+/// Posts the draft in `channel_id` where the captains pick. A draft without picks to make creates
+/// the teams right away instead. Any earlier draft in the guild is cancelled, its menu no longer
+/// accepts picks, the second value tells whether there was one.
+async fn start_draft(
+    discord: &dyn DiscordOps,
+    db: &Database,
+    drafts: &Drafts,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    draft: ActiveDraft,
+    audit_entries: &mut Vec<CreateEmbed>,
+) -> Result<(DraftStart, bool), String> {
+    let cancelled = drafts.drafts.lock().unwrap().remove(&guild_id).is_some();
+
+    // Like a lobby of only captains, or when all places left are in a single team
+    if draft.draft.picking().is_none() {
+        return create_teams(
+            discord,
+            db,
+            draft.user_id,
+            guild_id,
+            draft.channels,
+            draft.draft.teams,
+            audit_entries,
+        )
+        .await
+        .map(|teams| (DraftStart::Complete(teams), cancelled));
+    }
+
+    let (content, components) = draft_message(&draft);
+    let link = discord
+        .send_message(
+            channel_id,
+            CreateMessage::new().content(content).components(components),
+        )
+        .await?;
+    drafts.drafts.lock().unwrap().insert(guild_id, draft);
+    Ok((DraftStart::Posted(link), cancelled))
+}
+
+/// The teams so far, and a menu for the captain whose turn it is.
+fn draft_message(active: &ActiveDraft) -> (String, Vec<CreateActionRow>) {
+    let locale = active.locale.as_str();
+    let mut lines = vec![format!("**{}**", tr(locale, "shuffle.draft.title"))];
+    for (n, team) in active.draft.teams.iter().enumerate() {
+        let members: Vec<String> = team
+            .iter()
+            .map(|member| Mention::from(member.user_id).to_string())
+            .collect();
+        lines.push(tr_args(
+            locale,
+            "shuffle.draft.team",
+            &[
+                ("n", &(n + 1).to_string()),
+                ("members", &members.join(", ")),
+            ],
+        ));
+    }
+
+    let Some(team) = active.draft.picking() else {
+        lines.push(String::new());
+        lines.push(tr(locale, "shuffle.draft.done").to_string());
+        return (lines.join("\n"), Vec::new());
+    };
+    let captain = Mention::from(active.draft.teams[team][0].user_id).to_string();
+    lines.push(String::new());
+    lines.push(tr_args(
+        locale,
+        "shuffle.draft.turn",
+        &[("captain", &captain)],
+    ));
+
+    let options = active
+        .draft
+        .remaining
+        .iter()
+        // Lobbies this big don't fit in one menu, the rest can be picked once there is room
+        .take(MAX_SELECT_OPTIONS)
+        .map(|member| CreateSelectMenuOption::new(&member.name, member.user_id.to_string()))
+        .collect();
+    let menu = CreateSelectMenu::new(
+        format!("shuffle:draft:{}", active.id),
+        CreateSelectMenuKind::String { options },
+    )
+    .placeholder(tr(locale, "shuffle.draft.placeholder"));
+    (lines.join("\n"), vec![CreateActionRow::SelectMenu(menu)])
+}
+
+/// Handles a pick from the draft menu, and creates the teams once the draft is complete.
+async fn pick(
+    component: &ComponentInteraction,
+    handler: &Handler,
+    ctx: &Context,
+) -> Result<(), String> {
+    let locale = component.locale.as_str();
+    let expired = || tr(locale, "shuffle.draft.expired").to_string();
+
+    let Some(guild_id) = component.guild_id else {
+        return Err(tr(locale, "common.guild_only").to_string());
+    };
+    let draft_id = component
+        .data
+        .custom_id
+        .strip_prefix("shuffle:draft:")
+        .and_then(|id| id.parse::<u64>().ok());
+    let picked = match &component.data.kind {
+        ComponentInteractionDataKind::StringSelect { values } => values
+            .first()
+            .and_then(|value| value.parse::<u64>().ok())
+            .filter(|id| *id != 0)
+            .map(UserId::new),
+        _ => None,
+    };
+    let (Some(draft_id), Some(picked)) = (draft_id, picked) else {
+        return Err(expired());
+    };
+
+    // The lock can't be held across awaits, the draft is taken out once complete
+    let (content, components, complete) = {
+        let mut drafts = handler.drafts.drafts.lock().unwrap();
+        let Some(active) = drafts
+            .get_mut(&guild_id)
+            .filter(|active| active.id == draft_id)
+        else {
+            return Err(expired());
+        };
+        let Some(team) = active.draft.picking() else {
+            return Err(expired());
+        };
+        let captain = active.draft.teams[team][0].user_id;
+        if component.user.id != captain {
+            return Err(tr_args(
+                locale,
+                "shuffle.draft.not_your_turn",
+                &[("captain", &Mention::from(captain).to_string())],
+            ));
+        }
+        let Some(index) = active
+            .draft
+            .remaining
+            .iter()
+            .position(|member| member.user_id == picked)
+        else {
+            return Err(expired());
+        };
+        active.draft.pick(index);
+
+        let (content, components) = draft_message(active);
+        let complete = match active.draft.picking() {
+            Some(_) => None,
+            None => drafts.remove(&guild_id),
+        };
+        (content, components, complete)
+    };
+
+    let data = CreateInteractionResponseMessage::new()
+        .content(content)
+        .components(components);
+    component
+        .create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(data))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let Some(active) = complete else {
+        return Ok(());
+    };
+    let discord = SerenityDiscord::new(ctx);
+    let mut audit_entries = Vec::new();
+    let teams = create_teams(
+        &discord,
+        &handler.db,
+        active.user_id,
+        guild_id,
+        active.channels,
+        active.draft.teams,
+        &mut audit_entries,
+    )
+    .await;
+    audit::log(&discord, &handler.db, guild_id, audit_entries).await;
+
+    // The message was already updated, problems are sent to whoever made the last pick
+    let note = match teams {
+        Ok(teams) => finish(
+            &discord,
+            &handler.db,
+            &active.locale,
+            active.user_id,
+            guild_id,
+            &teams,
+            active.auto_regroup_after,
+        ),
+        Err(e) => Some(e),
+    };
+    if let Some(note) = note {
+        let followup = CreateInteractionResponseFollowup::new()
+            .content(note)
+            .ephemeral(true);
+        if let Err(e) = component.create_followup(&ctx.http, followup).await {
+            error!(error = %e, "Cannot send draft followup");
         }
     }
+    Ok(())
+}
+
+/// Moves the lobby into new team channels, returns who was moved to which team.
+#[allow(clippy::too_many_arguments)]
+async fn shuffle(
+    discord: &dyn DiscordOps,
+    db: &Database,
+    locale: &str,
+    user_id: UserId,
+    settings: &GuildSettings,
+    (shuffle_category_id, lobby_channel_id): (ChannelId, ChannelId),
+    layout: &Layout,
+    mode: Mode,
+    teammates: &Teammates<UserId>,
+    audit_entries: &mut Vec<CreateEmbed>,
+) -> Result<Vec<Vec<UserId>>, String> {
+    let (members_in_lobby, sizes) =
+        lobby_teams(discord, locale, settings.guild_id, lobby_channel_id, layout).await?;
+
+    let user_id_of = |member: &VoiceMember| member.user_id;
+    let teams: Vec<Vec<VoiceMember>> = match mode {
+        Mode::Random => teammates.fewest_repeats(user_id_of, || {
+            teams::random(members_in_lobby.clone(), &sizes, &mut rng())
+        }),
+        Mode::Balanced(ratings) => teammates.fewest_repeats(user_id_of, || {
+            teams::balanced(
+                members_in_lobby.clone(),
+                |member| {
+                    ratings
+                        .get(&member.user_id)
+                        .copied()
+                        .unwrap_or(DEFAULT_RATING)
+                },
+                &sizes,
+                &mut rng(),
+            )
+        }),
+    };
 
-    if members_in_lobby.len() < 1 {
-        return "There is nobody in the lobby".to_string();
+    create_teams(
+        discord,
+        db,
+        user_id,
+        settings.guild_id,
+        (shuffle_category_id, lobby_channel_id),
+        teams,
+        audit_entries,
+    )
+    .await
+}
+
+/// The members in the lobby and the sizes of their teams, checked before touching any channels.
+async fn lobby_teams(
+    discord: &dyn DiscordOps,
+    locale: &str,
+    guild_id: GuildId,
+    lobby_channel_id: ChannelId,
+    layout: &Layout,
+) -> Result<(Vec<VoiceMember>, Vec<usize>), String> {
+    let members_in_lobby = discord.voice_members(guild_id, lobby_channel_id).await?;
+
+    if members_in_lobby.is_empty() {
+        return Err(tr(locale, "shuffle.nobody_in_lobby").to_string());
     }
+    let members = members_in_lobby.len().to_string();
+    match layout.sizes(members_in_lobby.len()) {
+        Ok(sizes) => Ok((members_in_lobby, sizes)),
+        Err(LayoutError::TooFewMembers { n_teams }) => Err(tr_args(
+            locale,
+            "shuffle.too_few_members",
+            &[("members", &members), ("n_teams", &n_teams.to_string())],
+        )),
+        Err(LayoutError::TooManyTeams { n_teams }) => Err(tr_args(
+            locale,
+            "shuffle.too_many_teams",
+            &[
+                ("n_teams", &n_teams.to_string()),
+                ("max", &teams::MAX_TEAMS.to_string()),
+            ],
+        )),
+        Err(LayoutError::FormatMismatch { needed }) => Err(tr_args(
+            locale,
+            "shuffle.format_mismatch",
+            &[("members", &members), ("needed", &needed.to_string())],
+        )),
+    }
+}
 
-    let mut team_channels: Vec<GuildChannel> = Vec::new();
+/// Replaces the old team channels with a channel for each team and moves the members into them,
+/// returns who was moved to which team.
+async fn create_teams(
+    discord: &dyn DiscordOps,
+    db: &Database,
+    user_id: UserId,
+    guild_id: GuildId,
+    (shuffle_category_id, lobby_channel_id): (ChannelId, ChannelId),
+    teams: Vec<Vec<VoiceMember>>,
+    audit_entries: &mut Vec<CreateEmbed>,
+) -> Result<Vec<Vec<UserId>>, String> {
+    let channels = discord.channels(guild_id).await?;
 
-    for n in 0..n_teams {
-        let builder = CreateChannel::new(format!("team {}", n + 1))
-            .kind(ChannelType::Voice)
-            .category(handler.shuffle_category_id);
-        match handler.guild_id.create_channel(&ctx.http, builder).await {
-            Ok(channel) => team_channels.push(channel),
-            Err(e) => return e.to_string(),
+    for channel in channels {
+        if channel.parent_id == Some(shuffle_category_id) && channel.id != lobby_channel_id {
+            let action = format!("Deleted channel {}", channel.name);
+            match discord.delete_channel(channel.id).await {
+                Ok(()) => {
+                    forget_team_channel(db, channel.id);
+                    audit_entries.push(audit::entry(
+                        user_id,
+                        &action,
+                        Outcome::Success(String::new()),
+                    ))
+                }
+                Err(e) => {
+                    audit_entries.push(audit::entry(user_id, &action, Outcome::Failure(e.clone())));
+                    return Err(e);
+                }
+            }
         }
     }
 
-    members_in_lobby.shuffle(&mut rng());
+    let mut team_channels: Vec<(ChannelId, String)> = Vec::new();
 
-    for (i, members) in members_in_lobby
-        .chunks((members_in_lobby.len() as f32 / n_teams as f32).ceil() as usize)
-        .enumerate()
-    {
+    for n in 0..teams.len() {
+        let name = format!("team {}", n + 1);
+        let action = format!("Created channel {name}");
+        match discord
+            .create_voice_channel(guild_id, &name, shuffle_category_id)
+            .await
+        {
+            Ok(channel_id) => {
+                // Without it the channel is only deleted by the next shuffle or regroup
+                if let Err(e) = db.add_team_channel(guild_id, channel_id) {
+                    error!(error = %e, "Cannot track team channel");
+                }
+                audit_entries.push(audit::entry(
+                    user_id,
+                    &action,
+                    Outcome::Success(Mention::from(channel_id).to_string()),
+                ));
+                team_channels.push((channel_id, name));
+            }
+            Err(e) => {
+                audit_entries.push(audit::entry(user_id, &action, Outcome::Failure(e.clone())));
+                return Err(e);
+            }
+        }
+    }
+
+    let mut moved = Vec::new();
+    for ((channel_id, channel_name), members) in team_channels.iter().zip(teams) {
+        let mut team = Vec::new();
         for member in members {
-            member
-                .move_to_voice_channel(&ctx.http, &team_channels[i])
+            let action = format!("Moved {} to {channel_name}", member.name);
+            let outcome = match discord
+                .move_member(guild_id, member.user_id, *channel_id)
                 .await
-                .ok();
+            {
+                Ok(()) => {
+                    METRICS.members_moved.inc();
+                    team.push(member.user_id);
+                    Outcome::Success(Mention::from(member.user_id).to_string())
+                }
+                Err(e) => Outcome::Failure(e),
+            };
+            audit_entries.push(audit::entry(user_id, &action, outcome));
         }
+        moved.push(team);
     }
 
-    format!("Shuffling into {n_teams} teams").to_string()
+    Ok(moved)
 }
 
-pub fn register() -> CreateCommand {
-    let n_teams_option =
-        CreateCommandOption::new(CommandOptionType::Integer, "n_teams", "Number of teams")
-            .min_int_value(2)
-            .max_int_value(10)
-            .required(true);
+/// Stops tracking a deleted team channel, a failure only costs a pointless cleanup attempt.
+pub fn forget_team_channel(db: &Database, channel_id: ChannelId) {
+    if let Err(e) = db.remove_team_channel(channel_id) {
+        error!(error = %e, "Cannot forget team channel");
+    }
+}
+
+fn register() -> CreateCommand {
+    let n_teams_option = i18n::option(CommandOptionType::Integer, "n_teams", "shuffle.n_teams")
+        .min_int_value(2)
+        .max_int_value(teams::MAX_TEAMS as u64);
+    let team_size_option =
+        i18n::option(CommandOptionType::Integer, "team_size", "shuffle.team_size")
+            .min_int_value(1)
+            .max_int_value(25);
+    let format_option =
+        i18n::option(CommandOptionType::String, "format", "shuffle.format").max_length(40);
+    let mode_option = i18n::option(CommandOptionType::String, "mode", "shuffle.mode");
+    let mode_option = i18n::string_choice(mode_option, "shuffle.mode.random", "random");
+    let mode_option = i18n::string_choice(mode_option, "shuffle.mode.balanced", "balanced");
+    let mode_option = i18n::string_choice(mode_option, "shuffle.mode.captains", "captains");
+    let captains_option =
+        i18n::option(CommandOptionType::String, "captains", "shuffle.captains").max_length(500);
+    let auto_regroup_after_option = i18n::option(
+        CommandOptionType::Integer,
+        "auto_regroup_after",
+        "shuffle.auto_regroup_after",
+    )
+    .min_int_value(1)
+    .max_int_value(MAX_AUTO_REGROUP_AFTER);
 
-    CreateCommand::new("shuffle")
-        .name("shuffle")
-        .description("Shuffle users to different voice channels. Useful for playing against eachother in random teams")
+    i18n::command("shuffle", "shuffle")
         .add_option(n_teams_option)
+        .add_option(team_size_option)
+        .add_option(format_option)
+        .add_option(mode_option)
+        .add_option(captains_option)
+        .add_option(auto_regroup_after_option)
+}
+
+pub struct Shuffle;
+
+#[async_trait]
+impl SlashCommand for Shuffle {
+    fn name(&self) -> &'static str {
+        "shuffle"
+    }
+
+    fn permissions(&self) -> Permissions {
+        Permissions::MOVE_MEMBERS | Permissions::MANAGE_CHANNELS
+    }
+
+    fn register(&self) -> CreateCommand {
+        register()
+    }
+
+    async fn run(
+        &self,
+        command: &CommandInteraction,
+        handler: &Handler,
+        ctx: &Context,
+    ) -> Result<String, String> {
+        run(command, handler, ctx).await
+    }
+
+    async fn component(
+        &self,
+        component: &ComponentInteraction,
+        handler: &Handler,
+        ctx: &Context,
+    ) -> Result<(), String> {
+        pick(component, handler, ctx).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::model::prelude::GuildId;
+
+    use super::*;
+    use crate::discord::fake::FakeDiscord;
+
+    const CATEGORY: u64 = 10;
+    const LOBBY: u64 = 11;
+    const OLD_TEAM: u64 = 12;
+    const ELSEWHERE: u64 = 20;
+
+    fn guild() -> (FakeDiscord, GuildSettings) {
+        let discord = FakeDiscord::default();
+        discord.add_channel(CATEGORY, "shuffle", None);
+        discord.add_channel(LOBBY, "lobby", Some(CATEGORY));
+        discord.add_channel(OLD_TEAM, "team 1", Some(CATEGORY));
+        discord.add_channel(ELSEWHERE, "general", None);
+
+        let mut settings = GuildSettings::new(GuildId::new(1));
+        settings.shuffle_category_id = Some(ChannelId::new(CATEGORY));
+        settings.lobby_channel_id = Some(ChannelId::new(LOBBY));
+        (discord, settings)
+    }
+
+    async fn run_shuffle(
+        discord: &FakeDiscord,
+        settings: &GuildSettings,
+        layout: Layout,
+        mode: Mode,
+    ) -> Result<Vec<Vec<UserId>>, String> {
+        shuffle(
+            discord,
+            &Database::open(":memory:").unwrap(),
+            "en-US",
+            UserId::new(99),
+            settings,
+            (ChannelId::new(CATEGORY), ChannelId::new(LOBBY)),
+            &layout,
+            mode,
+            &Teammates::new(&[]),
+            &mut Vec::new(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn moves_everyone_in_the_lobby_into_teams() {
+        let (discord, settings) = guild();
+        for user_id in 1..=5 {
+            discord.connect(user_id, &format!("member {user_id}"), LOBBY);
+        }
+
+        let teams = run_shuffle(&discord, &settings, Layout::Teams(2), Mode::Random)
+            .await
+            .unwrap();
+
+        let state = discord.state.lock().unwrap();
+        assert_eq!(state.deleted_channels, vec![ChannelId::new(OLD_TEAM)]);
+        assert_eq!(state.created_channels.len(), 2);
+        assert_eq!(state.moves.len(), 5);
+        let created = state.created_channels.clone();
+        drop(state);
+
+        let mut sizes: Vec<usize> = created
+            .iter()
+            .map(|channel_id| discord.members_in(*channel_id).len())
+            .collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![2, 3]);
+        assert!(discord.members_in(ChannelId::new(LOBBY)).is_empty());
+        // The returned teams are the ones that were moved into team 1 and team 2
+        for (team, channel_id) in teams.iter().zip(&created) {
+            let mut in_channel = discord.members_in(*channel_id);
+            in_channel.sort();
+            let mut team = team.clone();
+            team.sort();
+            assert_eq!(team, in_channel);
+        }
+    }
+
+    #[tokio::test]
+    async fn leaves_channels_outside_the_category_alone() {
+        let (discord, settings) = guild();
+        discord.connect(1, "member 1", LOBBY);
+        discord.connect(2, "member 2", LOBBY);
+
+        run_shuffle(&discord, &settings, Layout::Teams(2), Mode::Random)
+            .await
+            .unwrap();
+
+        let state = discord.state.lock().unwrap();
+        assert!(state
+            .channels
+            .iter()
+            .any(|channel| channel.id == ChannelId::new(ELSEWHERE)));
+        assert!(state
+            .channels
+            .iter()
+            .any(|channel| channel.id == ChannelId::new(LOBBY)));
+    }
+
+    #[tokio::test]
+    async fn refuses_an_empty_lobby() {
+        let (discord, settings) = guild();
+        discord.connect(1, "member", ELSEWHERE);
+
+        let reply = run_shuffle(&discord, &settings, Layout::Teams(2), Mode::Random).await;
+
+        assert_eq!(reply, Err("There is nobody in the lobby".to_string()));
+        let state = discord.state.lock().unwrap();
+        assert!(state.created_channels.is_empty());
+        assert!(state.moves.is_empty());
+    }
+
+    #[tokio::test]
+    async fn balanced_mode_spreads_the_strongest_members() {
+        let (discord, settings) = guild();
+        let mut ratings = HashMap::new();
+        for user_id in 1..=4 {
+            discord.connect(user_id, &format!("member {user_id}"), LOBBY);
+        }
+        ratings.insert(UserId::new(1), 2500.0);
+        ratings.insert(UserId::new(2), 2500.0);
+
+        run_shuffle(
+            &discord,
+            &settings,
+            Layout::Teams(2),
+            Mode::Balanced(ratings),
+        )
+        .await
+        .unwrap();
+
+        let created = discord.state.lock().unwrap().created_channels.clone();
+        for channel_id in created {
+            let members = discord.members_in(channel_id);
+            assert_eq!(members.len(), 2);
+            assert_eq!(
+                members.iter().filter(|user_id| user_id.get() <= 2).count(),
+                1
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_more_teams_than_members_before_touching_channels() {
+        let (discord, settings) = guild();
+        for user_id in 1..=3 {
+            discord.connect(user_id, &format!("member {user_id}"), LOBBY);
+        }
+
+        let reply = run_shuffle(&discord, &settings, Layout::Teams(4), Mode::Random).await;
+
+        assert_eq!(
+            reply,
+            Err("There are only 3 members in the lobby, too few for 4 teams".to_string())
+        );
+        let state = discord.state.lock().unwrap();
+        assert!(state.deleted_channels.is_empty());
+        assert!(state.created_channels.is_empty());
+    }
+
+    #[tokio::test]
+    async fn follows_an_explicit_format() {
+        let (discord, settings) = guild();
+        for user_id in 1..=8 {
+            discord.connect(user_id, &format!("member {user_id}"), LOBBY);
+        }
+
+        let teams = run_shuffle(
+            &discord,
+            &settings,
+            Layout::Format(vec![3, 3, 2]),
+            Mode::Random,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            teams.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![3, 3, 2]
+        );
+        assert_eq!(discord.state.lock().unwrap().created_channels.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn tracks_the_team_channels_it_creates() {
+        let (discord, settings) = guild();
+        let db = Database::open(":memory:").unwrap();
+        db.add_team_channel(settings.guild_id, ChannelId::new(OLD_TEAM))
+            .unwrap();
+        discord.connect(1, "member 1", LOBBY);
+        discord.connect(2, "member 2", LOBBY);
+
+        shuffle(
+            &discord,
+            &db,
+            "en-US",
+            UserId::new(99),
+            &settings,
+            (ChannelId::new(CATEGORY), ChannelId::new(LOBBY)),
+            &Layout::Teams(2),
+            Mode::Random,
+            &Teammates::new(&[]),
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!db.is_team_channel(ChannelId::new(OLD_TEAM)).unwrap());
+        let created = discord.state.lock().unwrap().created_channels.clone();
+        for channel_id in created {
+            assert!(db.is_team_channel(channel_id).unwrap());
+        }
+    }
+
+    fn member(user_id: u64) -> VoiceMember {
+        VoiceMember {
+            user_id: UserId::new(user_id),
+            name: format!("member {user_id}"),
+        }
+    }
+
+    #[test]
+    fn parses_captain_mentions() {
+        assert_eq!(
+            parse_mentions("<@1> <@!2>,<@3>"),
+            Some(vec![UserId::new(1), UserId::new(2), UserId::new(3)])
+        );
+        assert_eq!(parse_mentions("<@1> someone"), None);
+        assert_eq!(parse_mentions("  "), None);
+    }
+
+    #[test]
+    fn picks_chosen_or_random_captains() {
+        let lobby: Vec<VoiceMember> = (1..=4).map(member).collect();
+
+        let (captains, members) =
+            pick_captains("en-US", lobby.clone(), &[UserId::new(3), UserId::new(1)], 2).unwrap();
+        assert_eq!(captains, vec![member(3), member(1)]);
+        assert_eq!(members, vec![member(2), member(4)]);
+
+        let (captains, members) = pick_captains("en-US", lobby.clone(), &[], 2).unwrap();
+        assert_eq!((captains.len(), members.len()), (2, 2));
+
+        assert_eq!(
+            pick_captains("en-US", lobby.clone(), &[UserId::new(1)], 2),
+            Err("Give 2 captains, one for each team".to_string())
+        );
+        assert_eq!(
+            pick_captains("en-US", lobby, &[UserId::new(1), UserId::new(9)], 2),
+            Err("<@9> is not in the lobby".to_string())
+        );
+    }
+
+    /// A 2v2 draft started by the /shuffle interaction `id`.
+    fn two_by_two(id: u64) -> ActiveDraft {
+        ActiveDraft {
+            id,
+            locale: "en-US".to_string(),
+            user_id: UserId::new(99),
+            channels: (ChannelId::new(CATEGORY), ChannelId::new(LOBBY)),
+            auto_regroup_after: None,
+            draft: Draft::new(
+                vec![member(1), member(2)],
+                vec![member(3), member(4)],
+                &[2, 2],
+            )
+            .unwrap(),
+        }
+    }
+
+    async fn start(
+        discord: &FakeDiscord,
+        drafts: &Drafts,
+        draft: ActiveDraft,
+    ) -> Result<(DraftStart, bool), String> {
+        start_draft(
+            discord,
+            &Database::open(":memory:").unwrap(),
+            drafts,
+            GuildId::new(1),
+            ChannelId::new(ELSEWHERE),
+            draft,
+            &mut Vec::new(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn posts_the_draft_for_the_first_captain() {
+        let discord = FakeDiscord::default();
+        let drafts = Drafts::default();
+
+        let started = start(&discord, &drafts, two_by_two(7)).await.unwrap();
+
+        assert!(matches!(started, (DraftStart::Posted(_), false)));
+        let messages = discord.messages_in(ChannelId::new(ELSEWHERE));
+        assert_eq!(messages.len(), 1);
+        let content = messages[0]["content"].as_str().unwrap();
+        assert!(content.contains("**Team 1**: <@1>"));
+        assert!(content.contains("<@1>, pick a member"));
+        let menu = &messages[0]["components"][0]["components"][0];
+        assert_eq!(menu["custom_id"], "shuffle:draft:7");
+        assert_eq!(menu["options"].as_array().unwrap().len(), 2);
+        assert!(drafts.drafts.lock().unwrap().contains_key(&GuildId::new(1)));
+    }
+
+    #[tokio::test]
+    async fn a_new_draft_cancels_the_one_in_progress() {
+        let discord = FakeDiscord::default();
+        let drafts = Drafts::default();
+        start(&discord, &drafts, two_by_two(7)).await.unwrap();
+
+        let started = start(&discord, &drafts, two_by_two(8)).await.unwrap();
+
+        assert!(matches!(started, (DraftStart::Posted(_), true)));
+        // Picks in the menu of the first draft are refused as expired
+        assert_eq!(drafts.drafts.lock().unwrap()[&GuildId::new(1)].id, 8);
+    }
+
+    #[tokio::test]
+    async fn moves_teams_right_away_when_there_is_nothing_to_pick() {
+        let (discord, _) = guild();
+        let drafts = Drafts::default();
+        discord.connect(1, "member 1", LOBBY);
+        discord.connect(2, "member 2", LOBBY);
+        discord.connect(3, "member 3", LOBBY);
+        // In a 1v2 the second captain gets the only member left without picking
+        let draft = ActiveDraft {
+            id: 7,
+            locale: "en-US".to_string(),
+            user_id: UserId::new(99),
+            channels: (ChannelId::new(CATEGORY), ChannelId::new(LOBBY)),
+            auto_regroup_after: None,
+            draft: Draft::new(vec![member(1), member(2)], vec![member(3)], &[1, 2]).unwrap(),
+        };
+
+        let started = start_draft(
+            &discord,
+            &Database::open(":memory:").unwrap(),
+            &drafts,
+            GuildId::new(1),
+            ChannelId::new(ELSEWHERE),
+            draft,
+            &mut Vec::new(),
+        )
+        .await
+        .unwrap();
+
+        let (DraftStart::Complete(teams), false) = started else {
+            panic!("Expected the draft to be complete");
+        };
+        assert_eq!(
+            teams,
+            vec![vec![UserId::new(1)], vec![UserId::new(2), UserId::new(3)]]
+        );
+        assert!(discord.messages_in(ChannelId::new(ELSEWHERE)).is_empty());
+        assert!(drafts.drafts.lock().unwrap().is_empty());
+        assert!(discord.members_in(ChannelId::new(LOBBY)).is_empty());
+    }
 }
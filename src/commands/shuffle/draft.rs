@@ -0,0 +1,145 @@
+/// Teams picked by captains in turns, in snake order: 1, 2, 3, 3, 2, 1, 1, 2, ...
+pub struct Draft<T> {
+    /// Each team starts with its captain
+    pub teams: Vec<Vec<T>>,
+    /// Members nobody picked yet
+    pub remaining: Vec<T>,
+    /// The team picking next and after that, and so on
+    order: Vec<usize>,
+}
+
+impl<T> Draft<T> {
+    /// A draft for teams of the given sizes, one for each captain. The sizes include the captain
+    /// and add up to the number of captains and members together.
+    pub fn new(captains: Vec<T>, members: Vec<T>, sizes: &[usize]) -> Result<Draft<T>, String> {
+        if captains.len() != sizes.len() {
+            return Err(format!(
+                "{} captains for {} teams",
+                captains.len(),
+                sizes.len()
+            ));
+        }
+        // Otherwise the picks below never run out
+        if sizes.iter().sum::<usize>() != captains.len() + members.len() {
+            return Err(format!(
+                "Teams of {sizes:?} for {} players",
+                captains.len() + members.len()
+            ));
+        }
+        let mut open: Vec<usize> = sizes.iter().map(|size| size.saturating_sub(1)).collect();
+        let mut order = Vec::new();
+        let mut round = 0;
+        while order.len() < members.len() {
+            for i in 0..sizes.len() {
+                let team = if round % 2 == 0 {
+                    i
+                } else {
+                    sizes.len() - 1 - i
+                };
+                if open[team] > 0 {
+                    open[team] -= 1;
+                    order.push(team);
+                }
+            }
+            round += 1;
+        }
+        // Picks are taken from the back
+        order.reverse();
+
+        let mut draft = Draft {
+            teams: captains.into_iter().map(|captain| vec![captain]).collect(),
+            remaining: members,
+            order,
+        };
+        draft.fill_last_team();
+        Ok(draft)
+    }
+
+    /// The team whose captain picks next, `None` once every member was picked.
+    pub fn picking(&self) -> Option<usize> {
+        self.order.last().copied()
+    }
+
+    /// Adds `remaining[index]` to the team that is picking.
+    pub fn pick(&mut self, index: usize) {
+        let Some(team) = self.order.pop() else {
+            return;
+        };
+        let member = self.remaining.remove(index);
+        self.teams[team].push(member);
+        self.fill_last_team();
+    }
+
+    /// Once a single team is left picking there is nothing to choose, it gets everyone left.
+    fn fill_last_team(&mut self) {
+        let Some(&team) = self.order.last() else {
+            return;
+        };
+        if self.order.iter().all(|&next| next == team) {
+            self.order.clear();
+            self.teams[team].append(&mut self.remaining);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captains_pick_in_snake_order() {
+        let mut draft = Draft::new(
+            vec!["a", "b", "c"],
+            vec!["1", "2", "3", "4", "5", "6"],
+            &[3, 3, 3],
+        )
+        .unwrap();
+
+        let mut picks = Vec::new();
+        while let Some(team) = draft.picking() {
+            picks.push(team);
+            draft.pick(0);
+        }
+
+        assert_eq!(picks, vec![0, 1, 2, 2, 1]);
+        assert_eq!(
+            draft.teams,
+            vec![
+                vec!["a", "1", "6"],
+                vec!["b", "2", "5"],
+                vec!["c", "3", "4"]
+            ]
+        );
+        assert!(draft.remaining.is_empty());
+    }
+
+    #[test]
+    fn full_teams_are_skipped() {
+        let mut draft = Draft::new(vec!["a", "b"], vec!["1", "2", "3", "4"], &[2, 4]).unwrap();
+
+        let mut picks = Vec::new();
+        while let Some(team) = draft.picking() {
+            picks.push(team);
+            draft.pick(0);
+        }
+
+        // Once team 1 is full, team 2 gets the rest without picking
+        assert_eq!(picks, vec![0]);
+        assert_eq!(draft.teams, vec![vec!["a", "1"], vec!["b", "2", "3", "4"]]);
+    }
+
+    #[test]
+    fn nothing_to_pick_with_only_captains() {
+        let draft: Draft<&str> = Draft::new(vec!["a", "b"], Vec::new(), &[1, 1]).unwrap();
+
+        assert_eq!(draft.picking(), None);
+        assert_eq!(draft.teams, vec![vec!["a"], vec!["b"]]);
+    }
+
+    #[test]
+    fn sizes_must_match_the_players() {
+        assert!(Draft::new(vec!["a"], vec!["1"], &[1, 1]).is_err());
+        assert!(Draft::new(vec!["a", "b"], vec!["1"], &[2, 2]).is_err());
+        assert!(Draft::new(vec!["a", "b"], vec!["1", "2", "3"], &[2, 2]).is_err());
+    }
+}
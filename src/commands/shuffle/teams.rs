@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngExt};
+
+/// How far ratings are randomly nudged before balancing, so the same lobby doesn't always end up
+/// in the same teams.
+const JITTER: f64 = 50.0;
+
+/// Rounds of swapping members between teams before settling for the teams as they are.
+const MAX_SWAPS: usize = 100;
+
+/// Splits tried when avoiding repeated teammates, the one with the fewest repeats is used.
+const CANDIDATES: usize = 20;
+
+/// Most teams a lobby can be split into.
+pub const MAX_TEAMS: usize = 10;
+
+/// How the lobby is split into teams.
+pub enum Layout {
+    /// This many teams of (nearly) equal size
+    Teams(usize),
+    /// As many teams as needed for teams of at most this size, of (nearly) equal size
+    TeamSize(usize),
+    /// Exactly these team sizes, like `3v3v2`
+    Format(Vec<usize>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LayoutError {
+    /// Some teams would be empty, or there would be a single team
+    TooFewMembers { n_teams: usize },
+    /// More than [`MAX_TEAMS`] teams
+    TooManyTeams { n_teams: usize },
+    /// The format is for a different number of members
+    FormatMismatch { needed: usize },
+}
+
+impl Layout {
+    /// Sizes of the teams for a lobby of `n_members`.
+    pub fn sizes(&self, n_members: usize) -> Result<Vec<usize>, LayoutError> {
+        let sizes = match self {
+            Layout::Teams(n_teams) => team_sizes(n_members, *n_teams),
+            Layout::TeamSize(size) => team_sizes(n_members, n_members.div_ceil(*size).max(1)),
+            Layout::Format(sizes) => {
+                let needed = sizes.iter().sum();
+                if needed != n_members {
+                    return Err(LayoutError::FormatMismatch { needed });
+                }
+                sizes.clone()
+            }
+        };
+        if sizes.len() > MAX_TEAMS {
+            return Err(LayoutError::TooManyTeams {
+                n_teams: sizes.len(),
+            });
+        }
+        if sizes.len() < 2 || sizes.contains(&0) {
+            return Err(LayoutError::TooFewMembers {
+                n_teams: sizes.len().max(2),
+            });
+        }
+        Ok(sizes)
+    }
+}
+
+/// Parses team sizes separated by a `v`, like `3v3v2`.
+pub fn parse_format(format: &str) -> Option<Vec<usize>> {
+    let sizes = format
+        .split(['v', 'V'])
+        .map(|size| size.trim().parse().ok().filter(|size| *size > 0))
+        .collect::<Option<Vec<usize>>>()?;
+    (2..=MAX_TEAMS).contains(&sizes.len()).then_some(sizes)
+}
+
+/// Splits members into teams of the given sizes at random.
+pub fn random<T>(mut members: Vec<T>, sizes: &[usize], rng: &mut impl Rng) -> Vec<Vec<T>> {
+    debug_assert_eq!(sizes.iter().sum::<usize>(), members.len());
+    members.shuffle(rng);
+    let mut teams: Vec<Vec<T>> = sizes.iter().map(|_| Vec::new()).collect();
+    let mut team = 0;
+    for member in members {
+        // Deal the members like cards, skipping the teams that are full
+        while teams[team].len() == sizes[team] {
+            team = (team + 1) % teams.len();
+        }
+        teams[team].push(member);
+        team = (team + 1) % teams.len();
+    }
+    teams
+}
+
+/// Splits members into teams of the given sizes with (nearly) equal total ratings.
+///
+/// Members are handed out strongest first to the team with the lowest total, after which members
+/// are swapped between teams for as long as that brings the totals closer together.
+pub fn balanced<T>(
+    members: Vec<T>,
+    rating: impl Fn(&T) -> f64,
+    sizes: &[usize],
+    rng: &mut impl Rng,
+) -> Vec<Vec<T>> {
+    let mut rated: Vec<(f64, T)> = members
+        .into_iter()
+        .map(|member| (rating(&member) + rng.random_range(-JITTER..=JITTER), member))
+        .collect();
+    rated.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    debug_assert_eq!(sizes.iter().sum::<usize>(), rated.len());
+    let mut teams: Vec<Vec<(f64, T)>> = sizes.iter().map(|_| Vec::new()).collect();
+    for member in rated {
+        let team = (0..teams.len())
+            .filter(|&i| teams[i].len() < sizes[i])
+            .min_by(|&a, &b| total(&teams[a]).total_cmp(&total(&teams[b])))
+            .expect("Team sizes add up to the number of members");
+        teams[team].push(member);
+    }
+
+    for _ in 0..MAX_SWAPS {
+        if !improve(&mut teams) {
+            break;
+        }
+    }
+
+    // Otherwise the first team would usually be the one with the strongest member
+    teams.shuffle(rng);
+    teams
+        .into_iter()
+        .map(|team| team.into_iter().map(|(_, member)| member).collect())
+        .collect()
+}
+
+/// How often pairs of members were teammates recently, more recent games weighing more.
+pub struct Teammates<K> {
+    weights: HashMap<(K, K), f64>,
+}
+
+impl<K: Copy + Ord + Hash> Teammates<K> {
+    /// From the teams of recent shuffles, most recent first.
+    pub fn new(history: &[Vec<Vec<K>>]) -> Teammates<K> {
+        let mut weights = HashMap::new();
+        for (age, teams) in history.iter().enumerate() {
+            let weight = (history.len() - age) as f64 / history.len() as f64;
+            for team in teams {
+                for (i, a) in team.iter().enumerate() {
+                    for b in &team[i + 1..] {
+                        *weights.entry(pair(*a, *b)).or_insert(0.0) += weight;
+                    }
+                }
+            }
+        }
+        Teammates { weights }
+    }
+
+    /// How much the teams repeat recent teammates.
+    fn repeats<T>(&self, teams: &[Vec<T>], key: &impl Fn(&T) -> K) -> f64 {
+        let mut repeats = 0.0;
+        for team in teams {
+            for (i, a) in team.iter().enumerate() {
+                for b in &team[i + 1..] {
+                    repeats += self.weights.get(&pair(key(a), key(b))).unwrap_or(&0.0);
+                }
+            }
+        }
+        repeats
+    }
+
+    /// Makes a number of splits with `split` and returns the one repeating the fewest recent
+    /// teammates, or just the first when there is nothing to avoid.
+    pub fn fewest_repeats<T>(
+        &self,
+        key: impl Fn(&T) -> K,
+        mut split: impl FnMut() -> Vec<Vec<T>>,
+    ) -> Vec<Vec<T>> {
+        let candidates = if self.weights.is_empty() {
+            1
+        } else {
+            CANDIDATES
+        };
+        (0..candidates)
+            .map(|_| split())
+            .min_by(|a, b| self.repeats(a, &key).total_cmp(&self.repeats(b, &key)))
+            .expect("There is at least one candidate")
+    }
+}
+
+/// The same key for a pair whichever way around.
+fn pair<K: Ord>(a: K, b: K) -> (K, K) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Sizes of `n_teams` teams for `n_members` members, differing by at most one.
+fn team_sizes(n_members: usize, n_teams: usize) -> Vec<usize> {
+    (0..n_teams)
+        .map(|i| n_members / n_teams + usize::from(i < n_members % n_teams))
+        .collect()
+}
+
+fn total<T>(team: &[(f64, T)]) -> f64 {
+    team.iter().map(|(rating, _)| rating).sum()
+}
+
+/// Difference between the strongest and the weakest team.
+fn spread(totals: &[f64]) -> f64 {
+    let max = totals.iter().copied().fold(f64::MIN, f64::max);
+    let min = totals.iter().copied().fold(f64::MAX, f64::min);
+    max - min
+}
+
+/// Makes the single swap of two members that reduces the spread the most, returns whether there
+/// was one.
+fn improve<T>(teams: &mut [Vec<(f64, T)>]) -> bool {
+    let totals: Vec<f64> = teams.iter().map(|team| total(team)).collect();
+    let current = spread(&totals);
+    let mut best = None;
+    let mut best_spread = current;
+
+    for a in 0..teams.len() {
+        for b in a + 1..teams.len() {
+            for (i, (rating_a, _)) in teams[a].iter().enumerate() {
+                for (j, (rating_b, _)) in teams[b].iter().enumerate() {
+                    let mut swapped = totals.clone();
+                    swapped[a] += rating_b - rating_a;
+                    swapped[b] += rating_a - rating_b;
+                    let swapped_spread = spread(&swapped);
+                    // Ignore rounding noise, or two equal members could be swapped forever
+                    if swapped_spread < best_spread - 1e-9 {
+                        best_spread = swapped_spread;
+                        best = Some((a, i, b, j));
+                    }
+                }
+            }
+        }
+    }
+
+    match best {
+        Some((a, i, b, j)) => {
+            let (left, right) = teams.split_at_mut(b);
+            std::mem::swap(&mut left[a][i], &mut right[0][j]);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn totals(teams: &[Vec<f64>]) -> Vec<f64> {
+        teams.iter().map(|team| team.iter().sum()).collect()
+    }
+
+    #[test]
+    fn team_sizes_differ_by_at_most_one() {
+        assert_eq!(team_sizes(7, 3), vec![3, 2, 2]);
+        assert_eq!(team_sizes(6, 2), vec![3, 3]);
+        assert_eq!(team_sizes(1, 2), vec![1, 0]);
+    }
+
+    #[test]
+    fn layouts_spread_members_evenly() {
+        assert_eq!(Layout::Teams(4).sizes(7), Ok(vec![2, 2, 2, 1]));
+        assert_eq!(Layout::Teams(4).sizes(5), Ok(vec![2, 1, 1, 1]));
+        assert_eq!(Layout::TeamSize(3).sizes(7), Ok(vec![3, 2, 2]));
+        assert_eq!(Layout::TeamSize(3).sizes(6), Ok(vec![3, 3]));
+        assert_eq!(Layout::Format(vec![3, 3, 2]).sizes(8), Ok(vec![3, 3, 2]));
+    }
+
+    #[test]
+    fn layouts_refuse_empty_teams() {
+        assert_eq!(
+            Layout::Teams(4).sizes(3),
+            Err(LayoutError::TooFewMembers { n_teams: 4 })
+        );
+        assert_eq!(
+            Layout::TeamSize(3).sizes(0),
+            Err(LayoutError::TooFewMembers { n_teams: 2 })
+        );
+        assert_eq!(
+            Layout::TeamSize(5).sizes(4),
+            Err(LayoutError::TooFewMembers { n_teams: 2 })
+        );
+        assert_eq!(
+            Layout::Format(vec![3, 3, 2]).sizes(7),
+            Err(LayoutError::FormatMismatch { needed: 8 })
+        );
+    }
+
+    #[test]
+    fn layouts_refuse_too_many_teams() {
+        assert_eq!(
+            Layout::TeamSize(1).sizes(25),
+            Err(LayoutError::TooManyTeams { n_teams: 25 })
+        );
+        assert_eq!(
+            Layout::TeamSize(1)
+                .sizes(MAX_TEAMS)
+                .map(|sizes| sizes.len()),
+            Ok(MAX_TEAMS)
+        );
+        assert_eq!(
+            Layout::Teams(MAX_TEAMS + 1).sizes(20),
+            Err(LayoutError::TooManyTeams {
+                n_teams: MAX_TEAMS + 1
+            })
+        );
+    }
+
+    #[test]
+    fn parses_formats() {
+        assert_eq!(parse_format("3v3v2"), Some(vec![3, 3, 2]));
+        assert_eq!(parse_format("1 V 2"), Some(vec![1, 2]));
+        assert_eq!(parse_format("5"), None);
+        assert_eq!(parse_format("3v0"), None);
+        assert_eq!(parse_format("3vv3"), None);
+        assert_eq!(parse_format(&["1"; MAX_TEAMS + 1].join("v")), None);
+    }
+
+    #[test]
+    fn random_fills_every_team() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let teams = random((0..5).collect(), &[2, 1, 1, 1], &mut rng);
+
+        assert_eq!(
+            teams.iter().map(Vec::len).collect::<Vec<_>>(),
+            vec![2, 1, 1, 1]
+        );
+        let mut members: Vec<i32> = teams.concat();
+        members.sort();
+        assert_eq!(members, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn balanced_teams_have_close_totals() {
+        let ratings = vec![1800.0, 1600.0, 1400.0, 1200.0, 1000.0, 800.0];
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let teams = balanced(ratings.clone(), |rating| *rating, &[3, 3], &mut rng);
+
+            assert_eq!(teams.iter().map(Vec::len).collect::<Vec<_>>(), vec![3, 3]);
+            // The best split is 200 apart, jitter may cost a little on top of that
+            assert!(spread(&totals(&teams)) <= 200.0 + 2.0 * 3.0 * JITTER);
+        }
+    }
+
+    #[test]
+    fn balanced_separates_the_strongest_members() {
+        let ratings = vec![2500.0, 2400.0, 1000.0, 1000.0, 1000.0, 1000.0];
+        let mut rng = StdRng::seed_from_u64(7);
+        let teams = balanced(ratings, |rating| *rating, &[3, 3], &mut rng);
+
+        assert!(teams
+            .iter()
+            .all(|team| team.iter().any(|rating| *rating > 2000.0)));
+    }
+
+    #[test]
+    fn recent_teammates_weigh_more() {
+        let teammates =
+            Teammates::new(&[vec![vec![1, 2], vec![3, 4]], vec![vec![1, 3], vec![2, 4]]]);
+
+        assert_eq!(teammates.repeats(&[vec![1, 2]], &|member| *member), 1.0);
+        assert_eq!(teammates.repeats(&[vec![3, 1]], &|member| *member), 0.5);
+        assert_eq!(
+            teammates.repeats(&[vec![1, 4], vec![2, 3]], &|member| *member),
+            0.0
+        );
+    }
+
+    #[test]
+    fn avoids_repeating_teammates() {
+        let members: Vec<u32> = (0..6).collect();
+        let last_time = vec![vec![0, 1, 2], vec![3, 4, 5]];
+        let teammates = Teammates::new(&[last_time]);
+
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let teams = teammates.fewest_repeats(
+                |member| *member,
+                || random(members.clone(), &[3, 3], &mut rng),
+            );
+            // At best each team has a single pair that played together last time
+            assert_eq!(teammates.repeats(&teams, &|member| *member), 2.0);
+        }
+    }
+
+    #[test]
+    fn balanced_teams_vary() {
+        let members: Vec<usize> = (0..8).collect();
+        let splits: Vec<Vec<Vec<usize>>> = (0..10)
+            .map(|seed| {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let mut teams = balanced(members.clone(), |_| 1000.0, &[4, 4], &mut rng);
+                for team in &mut teams {
+                    team.sort();
+                }
+                teams.sort();
+                teams
+            })
+            .collect();
+
+        assert!(splits.iter().any(|split| *split != splits[0]));
+    }
+}
@@ -0,0 +1,625 @@
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{NaiveTime, Weekday};
+use serde::Deserialize;
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+use tracing_subscriber::EnvFilter;
+
+use crate::api::tmdb::FALLBACK_LANGUAGE;
+use crate::utils::color::{parse_color, Rgb, Theme};
+
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawConfig {
+    discord: RawDiscord,
+    tmdb: RawTmdb,
+    jellyfin: RawJellyfin,
+    database: RawDatabase,
+    digest: RawDigest,
+    announcements: RawAnnouncements,
+    theme: RawTheme,
+    guild: RawGuild,
+    shuffle: RawShuffle,
+    logging: RawLogging,
+    http: RawHttp,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawDiscord {
+    token: Option<String>,
+    admin_user_id: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawTmdb {
+    token: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawJellyfin {
+    url: Option<String>,
+    api_key: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawDatabase {
+    path: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawDigest {
+    weekday: Option<String>,
+    time: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawAnnouncements {
+    language: Option<String>,
+    secondary_language: Option<String>,
+    spoiler_safe: Option<bool>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawTheme {
+    tmdb_movie: Option<String>,
+    tmdb_show: Option<String>,
+    tmdb_anime: Option<String>,
+    anilist_movie: Option<String>,
+    anilist_show: Option<String>,
+    anilist_anime: Option<String>,
+    digest: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawGuild {
+    id: Option<u64>,
+    announcements_channel_id: Option<u64>,
+    shuffle_category_id: Option<u64>,
+    lobby_channel_id: Option<u64>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawShuffle {
+    cleanup_after: Option<u64>,
+    history: Option<usize>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawLogging {
+    filter: Option<String>,
+    format: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawHttp {
+    address: Option<String>,
+}
+
+pub struct JellyfinConfig {
+    pub url: String,
+    pub api_key: String,
+}
+
+/// Guild configured from before multi-guild support, seeded into the database on startup.
+pub struct LegacyGuild {
+    pub guild_id: GuildId,
+    pub announcements_channel_id: Option<ChannelId>,
+    pub shuffle_category_id: Option<ChannelId>,
+    pub lobby_channel_id: Option<ChannelId>,
+}
+
+pub struct Config {
+    pub discord_token: String,
+    pub admin_user_id: UserId,
+    pub tmdb_token: String,
+    /// Only needed to check which items are in the library
+    pub jellyfin: Option<JellyfinConfig>,
+    pub database_path: String,
+    pub digest_weekday: Weekday,
+    pub digest_time: NaiveTime,
+    pub language: String,
+    pub secondary_language: Option<String>,
+    pub spoiler_safe: bool,
+    pub theme: Theme,
+    pub legacy_guild: Option<LegacyGuild>,
+    /// How long team channels are kept once they are empty, kept forever when unset
+    pub team_channel_cleanup: Option<Duration>,
+    /// Number of recent shuffles whose teammates are kept apart if possible
+    pub shuffle_history: usize,
+    /// `tracing` filter directives, like `info,alien_network_discord_bot=debug`
+    pub log_filter: String,
+    pub log_format: LogFormat,
+    /// Where to serve health checks and metrics, disabled when unset
+    pub http_address: Option<SocketAddr>,
+}
+
+#[derive(Clone, Copy)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, for log collectors
+    Json,
+}
+
+/// Collects every problem with the configuration instead of stopping at the first one.
+struct Loader<'a> {
+    /// Looks up environment variables
+    var: &'a dyn Fn(&str) -> Option<String>,
+    errors: Vec<String>,
+}
+
+impl Loader<'_> {
+    /// Reads `name` from the environment, or the contents of the file named by `name_FILE` so
+    /// secrets can be mounted as Docker secrets. Empty values count as unset.
+    fn env(&mut self, name: &str) -> Option<String> {
+        let var = |name: &str| (self.var)(name).filter(|value| !value.trim().is_empty());
+        if let Some(value) = var(name) {
+            return Some(value);
+        }
+        let path = var(&format!("{name}_FILE"))?;
+        match fs::read_to_string(&path) {
+            Ok(value) => Some(value.trim().to_string()).filter(|value| !value.is_empty()),
+            Err(e) => {
+                self.errors
+                    .push(format!("{name}_FILE: cannot read {path}: {e}"));
+                None
+            }
+        }
+    }
+
+    /// The value of the environment variable if set, else the one from the file.
+    fn value<T>(&mut self, from_file: Option<T>, env_name: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match self.env(env_name) {
+            Some(value) => match value.parse() {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    self.errors
+                        .push(format!("{env_name}: invalid value {value:?}: {e}"));
+                    None
+                }
+            },
+            None => from_file,
+        }
+    }
+
+    fn required<T>(&mut self, value: Option<T>, key: &str, env_name: &str) -> Option<T> {
+        if value.is_none() {
+            self.errors.push(format!(
+                "{key} is required, set it in the config file or {env_name}"
+            ));
+        }
+        value
+    }
+
+    fn parsed<T, E: Display>(
+        &mut self,
+        value: Option<String>,
+        key: &str,
+        parse: impl Fn(&str) -> Result<T, E>,
+    ) -> Option<T> {
+        let value = value?;
+        match parse(&value) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                self.errors
+                    .push(format!("{key}: invalid value {value:?}: {e}"));
+                None
+            }
+        }
+    }
+
+    fn color(&mut self, value: Option<String>, key: &str, default: Rgb) -> Rgb {
+        self.parsed(value, key, |value| {
+            parse_color(value).ok_or("not a colour, use #rrggbb, #rgb or a colour name")
+        })
+        .unwrap_or(default)
+    }
+}
+
+impl Config {
+    /// Loads the config file named by `CONFIG_PATH` (default `config.toml`, which may be absent)
+    /// and applies environment overrides. Returns every problem found when the result is invalid.
+    pub fn load() -> Result<Config, Vec<String>> {
+        let path = env::var("CONFIG_PATH").ok();
+        let raw = match fs::read_to_string(path.as_deref().unwrap_or(DEFAULT_CONFIG_PATH)) {
+            Ok(contents) => toml::from_str::<RawConfig>(&contents).map_err(|e| {
+                vec![format!(
+                    "{}: {e}",
+                    path.as_deref().unwrap_or(DEFAULT_CONFIG_PATH)
+                )]
+            })?,
+            // Everything can come from the environment, only complain about an explicit path
+            Err(_) if path.is_none() => RawConfig::default(),
+            Err(e) => return Err(vec![format!("{}: {e}", path.unwrap())]),
+        };
+
+        Config::from_raw(raw, &|name| env::var(name).ok())
+    }
+
+    /// Builds the config from the file contents, with overrides from the environment looked up by
+    /// `var`.
+    fn from_raw(
+        raw: RawConfig,
+        var: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Config, Vec<String>> {
+        let mut loader = Loader {
+            var,
+            errors: Vec::new(),
+        };
+
+        let discord_token = loader.value(raw.discord.token, "DISCORD_TOKEN");
+        let discord_token = loader.required(discord_token, "discord.token", "DISCORD_TOKEN");
+        let admin_user_id = loader.value(raw.discord.admin_user_id, "ADMIN_USER_ID");
+        let admin_user_id =
+            loader.required(admin_user_id, "discord.admin_user_id", "ADMIN_USER_ID");
+        let tmdb_token = loader.value(raw.tmdb.token, "TMDB_TOKEN");
+        let tmdb_token = loader.required(tmdb_token, "tmdb.token", "TMDB_TOKEN");
+
+        let jellyfin_url = loader.value(raw.jellyfin.url, "JELLYFIN_URL");
+        let jellyfin_api_key = loader.value(raw.jellyfin.api_key, "JELLYFIN_API_KEY");
+        let jellyfin = match (jellyfin_url, jellyfin_api_key) {
+            (Some(url), Some(api_key)) => Some(JellyfinConfig { url, api_key }),
+            (None, None) => None,
+            (Some(_), None) => {
+                loader
+                    .errors
+                    .push("jellyfin.api_key is required when jellyfin.url is set".to_string());
+                None
+            }
+            (None, Some(_)) => {
+                loader
+                    .errors
+                    .push("jellyfin.url is required when jellyfin.api_key is set".to_string());
+                None
+            }
+        };
+
+        let database_path = loader
+            .value(raw.database.path, "DATABASE_PATH")
+            .unwrap_or("alien-network.db".to_string());
+
+        let digest_weekday = loader.value(raw.digest.weekday, "DIGEST_WEEKDAY");
+        let digest_weekday = loader
+            .parsed(digest_weekday, "digest.weekday", |value| {
+                value
+                    .parse::<Weekday>()
+                    .map_err(|_| "not a day of the week")
+            })
+            .unwrap_or(Weekday::Sun);
+        let digest_time = loader.value(raw.digest.time, "DIGEST_TIME");
+        let digest_time = loader
+            .parsed(digest_time, "digest.time", |value| {
+                NaiveTime::parse_from_str(value, "%H:%M")
+            })
+            .unwrap_or(NaiveTime::from_hms_opt(18, 0, 0).unwrap());
+
+        let language = loader
+            .value(raw.announcements.language, "ANNOUNCEMENT_LANGUAGE")
+            .unwrap_or(FALLBACK_LANGUAGE.to_string());
+        let secondary_language = loader.value(
+            raw.announcements.secondary_language,
+            "ANNOUNCEMENT_SECONDARY_LANGUAGE",
+        );
+        let spoiler_safe = loader
+            .value(raw.announcements.spoiler_safe, "SPOILER_SAFE")
+            .unwrap_or(false);
+
+        let default_theme = Theme::default();
+        let mut theme = default_theme;
+        for (value, env_name, key, color, default) in [
+            (
+                raw.theme.tmdb_movie,
+                "THEME_TMDB_MOVIE",
+                "theme.tmdb_movie",
+                &mut theme.tmdb.movie,
+                default_theme.tmdb.movie,
+            ),
+            (
+                raw.theme.tmdb_show,
+                "THEME_TMDB_SHOW",
+                "theme.tmdb_show",
+                &mut theme.tmdb.show,
+                default_theme.tmdb.show,
+            ),
+            (
+                raw.theme.tmdb_anime,
+                "THEME_TMDB_ANIME",
+                "theme.tmdb_anime",
+                &mut theme.tmdb.anime,
+                default_theme.tmdb.anime,
+            ),
+            (
+                raw.theme.anilist_movie,
+                "THEME_ANILIST_MOVIE",
+                "theme.anilist_movie",
+                &mut theme.anilist.movie,
+                default_theme.anilist.movie,
+            ),
+            (
+                raw.theme.anilist_show,
+                "THEME_ANILIST_SHOW",
+                "theme.anilist_show",
+                &mut theme.anilist.show,
+                default_theme.anilist.show,
+            ),
+            (
+                raw.theme.anilist_anime,
+                "THEME_ANILIST_ANIME",
+                "theme.anilist_anime",
+                &mut theme.anilist.anime,
+                default_theme.anilist.anime,
+            ),
+            (
+                raw.theme.digest,
+                "THEME_DIGEST",
+                "theme.digest",
+                &mut theme.digest,
+                default_theme.digest,
+            ),
+        ] {
+            let value = loader.value(value, env_name);
+            *color = loader.color(value, key, default);
+        }
+
+        let guild_id = loader.value(raw.guild.id, "GUILD_ID");
+        let announcements_channel_id = loader.value(
+            raw.guild.announcements_channel_id,
+            "JELLYFIN_ANNOUNCEMENTS_CHANNEL_ID",
+        );
+        let shuffle_category_id =
+            loader.value(raw.guild.shuffle_category_id, "SHUFFLE_CATEGORY_ID");
+        let lobby_channel_id = loader.value(raw.guild.lobby_channel_id, "LOBBY_CHANNEL_ID");
+        if guild_id.is_none() {
+            for (key, id) in [
+                ("guild.announcements_channel_id", announcements_channel_id),
+                ("guild.shuffle_category_id", shuffle_category_id),
+                ("guild.lobby_channel_id", lobby_channel_id),
+            ] {
+                if id.is_some() {
+                    loader.errors.push(format!("{key} is set without guild.id"));
+                }
+            }
+        }
+
+        let team_channel_cleanup = loader
+            .value(raw.shuffle.cleanup_after, "SHUFFLE_CLEANUP_AFTER")
+            .unwrap_or(60);
+        let team_channel_cleanup =
+            (team_channel_cleanup > 0).then(|| Duration::from_secs(team_channel_cleanup));
+        let shuffle_history = loader
+            .value(raw.shuffle.history, "SHUFFLE_HISTORY")
+            .unwrap_or(5);
+
+        let log_filter = loader
+            .value(raw.logging.filter, "LOG_FILTER")
+            .unwrap_or("info".to_string());
+        if let Err(e) = EnvFilter::try_new(&log_filter) {
+            loader
+                .errors
+                .push(format!("logging.filter: invalid value {log_filter:?}: {e}"));
+        }
+        let log_format = loader.value(raw.logging.format, "LOG_FORMAT");
+        let log_format = loader
+            .parsed(log_format, "logging.format", |value| match value {
+                "text" => Ok(LogFormat::Text),
+                "json" => Ok(LogFormat::Json),
+                _ => Err("use text or json"),
+            })
+            .unwrap_or(LogFormat::Text);
+
+        let http_address = loader.value(raw.http.address, "HTTP_ADDRESS");
+        let http_address = loader.parsed(http_address, "http.address", |value| {
+            value.parse::<SocketAddr>()
+        });
+
+        for (key, id) in [
+            ("discord.admin_user_id", admin_user_id),
+            ("guild.id", guild_id),
+            ("guild.announcements_channel_id", announcements_channel_id),
+            ("guild.shuffle_category_id", shuffle_category_id),
+            ("guild.lobby_channel_id", lobby_channel_id),
+        ] {
+            if id == Some(0) {
+                loader.errors.push(format!("{key} cannot be 0"));
+            }
+        }
+
+        if !loader.errors.is_empty() {
+            return Err(loader.errors);
+        }
+
+        let legacy_guild = guild_id.map(|guild_id| LegacyGuild {
+            guild_id: GuildId::new(guild_id),
+            announcements_channel_id: announcements_channel_id.map(ChannelId::new),
+            shuffle_category_id: shuffle_category_id.map(ChannelId::new),
+            lobby_channel_id: lobby_channel_id.map(ChannelId::new),
+        });
+
+        Ok(Config {
+            discord_token: discord_token.unwrap(),
+            admin_user_id: UserId::new(admin_user_id.unwrap()),
+            tmdb_token: tmdb_token.unwrap(),
+            jellyfin,
+            database_path,
+            digest_weekday,
+            digest_time,
+            language,
+            secondary_language,
+            spoiler_safe,
+            theme,
+            legacy_guild,
+            team_channel_cleanup,
+            shuffle_history,
+            log_filter,
+            log_format,
+            http_address,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn load(toml: &str, vars: &[(&str, &str)]) -> Result<Config, Vec<String>> {
+        let raw = toml::from_str(toml).unwrap();
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        Config::from_raw(raw, &|name| vars.get(name).cloned())
+    }
+
+    const REQUIRED: &str = r#"
+        [discord]
+        token = "discord token"
+        admin_user_id = 1
+
+        [tmdb]
+        token = "tmdb token"
+    "#;
+
+    #[test]
+    fn loads_the_required_values() {
+        let config = load(REQUIRED, &[]).unwrap();
+
+        assert_eq!(config.discord_token, "discord token");
+        assert_eq!(config.admin_user_id, UserId::new(1));
+        assert_eq!(config.tmdb_token, "tmdb token");
+        assert_eq!(config.digest_weekday, Weekday::Sun);
+    }
+
+    #[test]
+    fn reports_every_missing_value() {
+        let errors = load("", &[]).err().unwrap();
+
+        assert_eq!(
+            errors,
+            vec![
+                "discord.token is required, set it in the config file or DISCORD_TOKEN",
+                "discord.admin_user_id is required, set it in the config file or ADMIN_USER_ID",
+                "tmdb.token is required, set it in the config file or TMDB_TOKEN",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_every_invalid_value() {
+        let toml = format!("{REQUIRED}\n[digest]\ntime = \"25:00\"");
+        let errors = load(&toml, &[("ADMIN_USER_ID", "me"), ("LOG_FORMAT", "xml")])
+            .err()
+            .unwrap();
+
+        assert_eq!(errors.len(), 4);
+        assert!(errors[0].starts_with("ADMIN_USER_ID: invalid value \"me\""));
+        // Without a valid admin the required check fails too
+        assert!(errors[1].starts_with("discord.admin_user_id is required"));
+        assert!(errors[2].starts_with("digest.time: invalid value \"25:00\""));
+        assert_eq!(
+            errors[3],
+            "logging.format: invalid value \"xml\": use text or json"
+        );
+    }
+
+    #[test]
+    fn environment_overrides_the_file() {
+        let config = load(
+            REQUIRED,
+            &[("DISCORD_TOKEN", "from env"), ("TMDB_TOKEN", " ")],
+        )
+        .unwrap();
+
+        assert_eq!(config.discord_token, "from env");
+        // Empty values count as unset
+        assert_eq!(config.tmdb_token, "tmdb token");
+    }
+
+    #[test]
+    fn empty_environment_values_are_missing() {
+        let errors = load(
+            "",
+            &[
+                ("DISCORD_TOKEN", ""),
+                ("ADMIN_USER_ID", "1"),
+                ("TMDB_TOKEN", "x"),
+            ],
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(
+            errors,
+            vec!["discord.token is required, set it in the config file or DISCORD_TOKEN"]
+        );
+    }
+
+    #[test]
+    fn reads_secrets_from_files() {
+        let path = env::temp_dir().join(format!("tmdb-token-{}", std::process::id()));
+        fs::write(&path, "secret from file\n").unwrap();
+
+        let config = load(REQUIRED, &[("TMDB_TOKEN_FILE", path.to_str().unwrap())]);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(config.unwrap().tmdb_token, "secret from file");
+    }
+
+    #[test]
+    fn reports_unreadable_secret_files() {
+        let errors = load(REQUIRED, &[("TMDB_TOKEN_FILE", "/nonexistent/token")])
+            .err()
+            .unwrap();
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("TMDB_TOKEN_FILE: cannot read /nonexistent/token"));
+    }
+
+    #[test]
+    fn theme_colours_default_when_unset() {
+        let config = load(REQUIRED, &[("THEME_TMDB_MOVIE", "#123456")]).unwrap();
+        assert_eq!(config.theme.tmdb.movie, (0x12, 0x34, 0x56));
+        assert_eq!(config.theme.digest, Theme::default().digest);
+
+        let errors = load(&format!("{REQUIRED}\n[theme]\ndigest = \"#12\""), &[])
+            .err()
+            .unwrap();
+        assert_eq!(
+            errors,
+            vec!["theme.digest: invalid value \"#12\": not a colour, use #rrggbb, #rgb or a colour name"]
+        );
+    }
+
+    #[test]
+    fn guild_channels_need_a_guild() {
+        let errors = load(REQUIRED, &[("LOBBY_CHANNEL_ID", "5")]).err().unwrap();
+
+        assert_eq!(
+            errors,
+            vec!["guild.lobby_channel_id is set without guild.id"]
+        );
+        assert!(load(REQUIRED, &[("LOBBY_CHANNEL_ID", "5"), ("GUILD_ID", "2")]).is_ok());
+    }
+}
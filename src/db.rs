@@ -0,0 +1,650 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serenity::model::prelude::{ChannelId, GuildId, RoleId, UserId};
+
+/// Schema changes, applied in order. The index + 1 of the last applied migration is stored in
+/// the `user_version` pragma, so never edit or reorder existing entries, only append.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS announcements (
+        id INTEGER PRIMARY KEY,
+        kind TEXT NOT NULL,
+        title TEXT NOT NULL,
+        posted_at INTEGER NOT NULL
+    );",
+    "ALTER TABLE announcements ADD COLUMN guild_id INTEGER;
+    CREATE TABLE guild_settings (
+        guild_id INTEGER PRIMARY KEY,
+        announcements_channel_id INTEGER,
+        shuffle_category_id INTEGER,
+        lobby_channel_id INTEGER
+    );",
+    "CREATE TABLE command_roles (
+        guild_id INTEGER NOT NULL,
+        command TEXT NOT NULL,
+        role_id INTEGER NOT NULL,
+        PRIMARY KEY (guild_id, command, role_id)
+    );",
+    "ALTER TABLE guild_settings ADD COLUMN audit_channel_id INTEGER;",
+    "CREATE TABLE member_ratings (
+        guild_id INTEGER NOT NULL,
+        user_id INTEGER NOT NULL,
+        rating REAL NOT NULL,
+        PRIMARY KEY (guild_id, user_id)
+    );",
+    "CREATE TABLE shuffles (
+        id INTEGER PRIMARY KEY,
+        guild_id INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        winner INTEGER
+    );
+    CREATE TABLE shuffle_members (
+        shuffle_id INTEGER NOT NULL REFERENCES shuffles (id),
+        user_id INTEGER NOT NULL,
+        team INTEGER NOT NULL,
+        PRIMARY KEY (shuffle_id, user_id)
+    );",
+    "CREATE TABLE team_channels (
+        channel_id INTEGER PRIMARY KEY,
+        guild_id INTEGER NOT NULL
+    );",
+];
+
+/// Rating of members who have not been rated yet.
+pub const DEFAULT_RATING: f64 = 1000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MediaKind {
+    Movie,
+    Show,
+    Anime,
+}
+
+impl MediaKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaKind::Movie => "movie",
+            MediaKind::Show => "show",
+            MediaKind::Anime => "anime",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<MediaKind> {
+        match s {
+            "movie" => Some(MediaKind::Movie),
+            "show" => Some(MediaKind::Show),
+            "anime" => Some(MediaKind::Anime),
+            _ => None,
+        }
+    }
+}
+
+/// Teams formed by a shuffle, and which of them won if that was recorded.
+#[derive(Debug)]
+pub struct Shuffle {
+    pub id: i64,
+    pub teams: Vec<Vec<UserId>>,
+    /// Index into `teams`
+    pub winner: Option<usize>,
+}
+
+/// Games with a recorded result a member played in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Record {
+    pub games: u32,
+    pub wins: u32,
+}
+
+#[derive(Debug)]
+pub struct Announcement {
+    pub kind: MediaKind,
+    pub title: String,
+}
+
+/// Configuration of the bot for a single guild. Unset values disable the commands that need them.
+#[derive(Clone, Debug)]
+pub struct GuildSettings {
+    pub guild_id: GuildId,
+    pub announcements_channel_id: Option<ChannelId>,
+    pub shuffle_category_id: Option<ChannelId>,
+    pub lobby_channel_id: Option<ChannelId>,
+    /// Receives a log of everything done through the bot
+    pub audit_channel_id: Option<ChannelId>,
+}
+
+impl GuildSettings {
+    pub fn new(guild_id: GuildId) -> GuildSettings {
+        GuildSettings {
+            guild_id,
+            announcements_channel_id: None,
+            shuffle_category_id: None,
+            lobby_channel_id: None,
+            audit_channel_id: None,
+        }
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<GuildSettings> {
+        let channel = |index: usize| -> rusqlite::Result<Option<ChannelId>> {
+            Ok(row
+                .get::<_, Option<i64>>(index)?
+                .map(|id| ChannelId::new(id as u64)))
+        };
+        Ok(GuildSettings {
+            guild_id: GuildId::new(row.get::<_, i64>(0)? as u64),
+            announcements_channel_id: channel(1)?,
+            shuffle_category_id: channel(2)?,
+            lobby_channel_id: channel(3)?,
+            audit_channel_id: channel(4)?,
+        })
+    }
+}
+
+/// Discord ids are u64 but SQLite only stores signed integers, the bits are kept as is.
+fn sql_id(id: Option<ChannelId>) -> Option<i64> {
+    id.map(|id| id.get() as i64)
+}
+
+/// Handle to the bot's SQLite database, cheap to clone.
+#[derive(Clone)]
+pub struct Database {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Database {
+    pub fn open(path: &str) -> Result<Database, String> {
+        let conn = Connection::open(path).map_err(|e| e.to_string())?;
+
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            conn.execute_batch(&format!(
+                "BEGIN; {migration} PRAGMA user_version = {}; COMMIT;",
+                i + 1
+            ))
+            .map_err(|e| format!("Migration {} failed: {e}", i + 1))?;
+        }
+
+        Ok(Database {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    pub fn record_announcement(
+        &self,
+        guild_id: GuildId,
+        kind: MediaKind,
+        title: &str,
+        posted_at: i64,
+    ) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO announcements (guild_id, kind, title, posted_at) VALUES (?1, ?2, ?3, ?4)",
+            params![guild_id.get() as i64, kind.as_str(), title, posted_at],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    /// Returns all announcements in a guild posted at or after `since`, oldest first.
+    pub fn announcements_since(
+        &self,
+        guild_id: GuildId,
+        since: i64,
+    ) -> Result<Vec<Announcement>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare(
+                "SELECT kind, title FROM announcements
+                WHERE guild_id = ?1 AND posted_at >= ?2 ORDER BY posted_at",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let rows = statement
+            .query_map(params![guild_id.get() as i64, since], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut announcements = Vec::new();
+        for row in rows {
+            let (kind, title) = row.map_err(|e| e.to_string())?;
+            if let Some(kind) = MediaKind::parse(&kind) {
+                announcements.push(Announcement { kind, title });
+            }
+        }
+        Ok(announcements)
+    }
+
+    /// Returns the settings of a guild, all unset if it was never configured.
+    pub fn guild_settings(&self, guild_id: GuildId) -> Result<GuildSettings, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT guild_id, announcements_channel_id, shuffle_category_id, lobby_channel_id,
+                audit_channel_id
+            FROM guild_settings WHERE guild_id = ?1",
+            params![guild_id.get() as i64],
+            GuildSettings::from_row,
+        )
+        .optional()
+        .map(|settings| settings.unwrap_or(GuildSettings::new(guild_id)))
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn all_guild_settings(&self) -> Result<Vec<GuildSettings>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare(
+                "SELECT guild_id, announcements_channel_id, shuffle_category_id, lobby_channel_id,
+                    audit_channel_id
+                FROM guild_settings",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map([], GuildSettings::from_row)
+            .map_err(|e| e.to_string())?;
+        rows.collect::<rusqlite::Result<Vec<GuildSettings>>>()
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn save_guild_settings(&self, settings: &GuildSettings) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO guild_settings
+                (guild_id, announcements_channel_id, shuffle_category_id, lobby_channel_id,
+                    audit_channel_id)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT (guild_id) DO UPDATE SET
+                announcements_channel_id = excluded.announcements_channel_id,
+                shuffle_category_id = excluded.shuffle_category_id,
+                lobby_channel_id = excluded.lobby_channel_id,
+                audit_channel_id = excluded.audit_channel_id",
+            params![
+                settings.guild_id.get() as i64,
+                sql_id(settings.announcements_channel_id),
+                sql_id(settings.shuffle_category_id),
+                sql_id(settings.lobby_channel_id),
+                sql_id(settings.audit_channel_id),
+            ],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    /// Assigns announcements recorded before the bot supported multiple guilds to `guild_id`.
+    pub fn adopt_unassigned_announcements(&self, guild_id: GuildId) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE announcements SET guild_id = ?1 WHERE guild_id IS NULL",
+            params![guild_id.get() as i64],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    /// Returns the roles allowed to use a command in a guild, as `(command, role)` pairs where
+    /// `command` is the command name followed by its subcommands, like `announce tmdb`.
+    pub fn command_roles(&self, guild_id: GuildId) -> Result<Vec<(String, RoleId)>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare(
+                "SELECT command, role_id FROM command_roles WHERE guild_id = ?1
+                ORDER BY command, role_id",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map(params![guild_id.get() as i64], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    RoleId::new(row.get::<_, i64>(1)? as u64),
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<rusqlite::Result<Vec<(String, RoleId)>>>()
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn add_command_role(
+        &self,
+        guild_id: GuildId,
+        command: &str,
+        role_id: RoleId,
+    ) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO command_roles (guild_id, command, role_id) VALUES (?1, ?2, ?3)",
+            params![guild_id.get() as i64, command, role_id.get() as i64],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    /// Returns whether the role was allowed to use the command.
+    pub fn remove_command_role(
+        &self,
+        guild_id: GuildId,
+        command: &str,
+        role_id: RoleId,
+    ) -> Result<bool, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM command_roles WHERE guild_id = ?1 AND command = ?2 AND role_id = ?3",
+            params![guild_id.get() as i64, command, role_id.get() as i64],
+        )
+        .map(|removed| removed > 0)
+        .map_err(|e| e.to_string())
+    }
+
+    /// Ratings of all rated members of a guild, members without one have [`DEFAULT_RATING`].
+    pub fn ratings(&self, guild_id: GuildId) -> Result<HashMap<UserId, f64>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare("SELECT user_id, rating FROM member_ratings WHERE guild_id = ?1")
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map(params![guild_id.get() as i64], |row| {
+                Ok((
+                    UserId::new(row.get::<_, i64>(0)? as u64),
+                    row.get::<_, f64>(1)?,
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<rusqlite::Result<HashMap<UserId, f64>>>()
+            .map_err(|e| e.to_string())
+    }
+
+    pub fn set_rating(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        rating: f64,
+    ) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO member_ratings (guild_id, user_id, rating) VALUES (?1, ?2, ?3)
+            ON CONFLICT (guild_id, user_id) DO UPDATE SET rating = excluded.rating",
+            params![guild_id.get() as i64, user_id.get() as i64, rating],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    /// Remembers who ended up in which team, returns the id of the shuffle.
+    pub fn record_shuffle(
+        &self,
+        guild_id: GuildId,
+        teams: &[Vec<UserId>],
+        created_at: i64,
+    ) -> Result<i64, String> {
+        let mut conn = self.conn.lock().unwrap();
+        let transaction = conn.transaction().map_err(|e| e.to_string())?;
+        transaction
+            .execute(
+                "INSERT INTO shuffles (guild_id, created_at) VALUES (?1, ?2)",
+                params![guild_id.get() as i64, created_at],
+            )
+            .map_err(|e| e.to_string())?;
+        let shuffle_id = transaction.last_insert_rowid();
+        for (team, members) in teams.iter().enumerate() {
+            for user_id in members {
+                transaction
+                    .execute(
+                        "INSERT INTO shuffle_members (shuffle_id, user_id, team) VALUES (?1, ?2, ?3)",
+                        params![shuffle_id, user_id.get() as i64, team as i64],
+                    )
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        transaction.commit().map_err(|e| e.to_string())?;
+        Ok(shuffle_id)
+    }
+
+    /// The most recent shuffle in a guild.
+    pub fn last_shuffle(&self, guild_id: GuildId) -> Result<Option<Shuffle>, String> {
+        let conn = self.conn.lock().unwrap();
+        let shuffle = conn
+            .query_row(
+                "SELECT id, winner FROM shuffles WHERE guild_id = ?1 ORDER BY id DESC LIMIT 1",
+                params![guild_id.get() as i64],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Option<i64>>(1)?)),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let Some((id, winner)) = shuffle else {
+            return Ok(None);
+        };
+
+        let teams = shuffle_teams(&conn, id)?;
+        Ok(Some(Shuffle {
+            id,
+            teams,
+            winner: winner.map(|winner| winner as usize),
+        }))
+    }
+
+    /// The teams of the last `limit` shuffles in a guild, most recent first.
+    pub fn recent_teams(
+        &self,
+        guild_id: GuildId,
+        limit: usize,
+    ) -> Result<Vec<Vec<Vec<UserId>>>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare("SELECT id FROM shuffles WHERE guild_id = ?1 ORDER BY id DESC LIMIT ?2")
+            .map_err(|e| e.to_string())?;
+        let ids = statement
+            .query_map(params![guild_id.get() as i64, limit as i64], |row| {
+                row.get::<_, i64>(0)
+            })
+            .map_err(|e| e.to_string())?
+            .collect::<rusqlite::Result<Vec<i64>>>()
+            .map_err(|e| e.to_string())?;
+        ids.into_iter().map(|id| shuffle_teams(&conn, id)).collect()
+    }
+
+    /// Stores the winner of a shuffle together with the ratings it led to. Returns `false` and
+    /// changes nothing when the shuffle already has a winner.
+    pub fn record_result(
+        &self,
+        guild_id: GuildId,
+        shuffle_id: i64,
+        winner: usize,
+        ratings: &HashMap<UserId, f64>,
+    ) -> Result<bool, String> {
+        let mut conn = self.conn.lock().unwrap();
+        let transaction = conn.transaction().map_err(|e| e.to_string())?;
+        let updated = transaction
+            .execute(
+                "UPDATE shuffles SET winner = ?1 WHERE id = ?2 AND winner IS NULL",
+                params![winner as i64, shuffle_id],
+            )
+            .map_err(|e| e.to_string())?;
+        if updated == 0 {
+            return Ok(false);
+        }
+        for (user_id, rating) in ratings {
+            transaction
+                .execute(
+                    "INSERT INTO member_ratings (guild_id, user_id, rating) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (guild_id, user_id) DO UPDATE SET rating = excluded.rating",
+                    params![guild_id.get() as i64, user_id.get() as i64, rating],
+                )
+                .map_err(|e| e.to_string())?;
+        }
+        transaction.commit().map_err(|e| e.to_string())?;
+        Ok(true)
+    }
+
+    /// Remembers a channel created for a team, so it can be cleaned up once it is empty.
+    pub fn add_team_channel(&self, guild_id: GuildId, channel_id: ChannelId) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO team_channels (channel_id, guild_id) VALUES (?1, ?2)",
+            params![channel_id.get() as i64, guild_id.get() as i64],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn is_team_channel(&self, channel_id: ChannelId) -> Result<bool, String> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM team_channels WHERE channel_id = ?1)",
+            params![channel_id.get() as i64],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    /// Every team channel that was not deleted yet, in any guild.
+    pub fn team_channels(&self) -> Result<Vec<(GuildId, ChannelId)>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare("SELECT guild_id, channel_id FROM team_channels")
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    GuildId::new(row.get::<_, i64>(0)? as u64),
+                    ChannelId::new(row.get::<_, i64>(1)? as u64),
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<rusqlite::Result<Vec<(GuildId, ChannelId)>>>()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Forgets a team channel after it was deleted.
+    pub fn remove_team_channel(&self, channel_id: ChannelId) -> Result<(), String> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM team_channels WHERE channel_id = ?1",
+            params![channel_id.get() as i64],
+        )
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+    }
+
+    /// Games played and won per member, counting only shuffles with a recorded winner.
+    pub fn records(&self, guild_id: GuildId) -> Result<HashMap<UserId, Record>, String> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn
+            .prepare(
+                "SELECT m.user_id, COUNT(*), SUM(m.team = s.winner) FROM shuffle_members m
+                JOIN shuffles s ON s.id = m.shuffle_id
+                WHERE s.guild_id = ?1 AND s.winner IS NOT NULL
+                GROUP BY m.user_id",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = statement
+            .query_map(params![guild_id.get() as i64], |row| {
+                Ok((
+                    UserId::new(row.get::<_, i64>(0)? as u64),
+                    Record {
+                        games: row.get(1)?,
+                        wins: row.get(2)?,
+                    },
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<rusqlite::Result<HashMap<UserId, Record>>>()
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Who was in which team in a shuffle.
+fn shuffle_teams(conn: &Connection, shuffle_id: i64) -> Result<Vec<Vec<UserId>>, String> {
+    let mut statement = conn
+        .prepare("SELECT user_id, team FROM shuffle_members WHERE shuffle_id = ?1 ORDER BY team")
+        .map_err(|e| e.to_string())?;
+    let rows = statement
+        .query_map(params![shuffle_id], |row| {
+            Ok((
+                UserId::new(row.get::<_, i64>(0)? as u64),
+                row.get::<_, i64>(1)? as usize,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut teams: Vec<Vec<UserId>> = Vec::new();
+    for row in rows {
+        let (user_id, team) = row.map_err(|e| e.to_string())?;
+        if teams.len() <= team {
+            teams.resize(team + 1, Vec::new());
+        }
+        teams[team].push(user_id);
+    }
+    Ok(teams)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: u64) -> UserId {
+        UserId::new(id)
+    }
+
+    #[test]
+    fn records_count_games_with_a_winner() {
+        let db = Database::open(":memory:").unwrap();
+        let guild_id = GuildId::new(1);
+        let teams = vec![vec![user(1), user(2)], vec![user(3)]];
+
+        let first = db.record_shuffle(guild_id, &teams, 100).unwrap();
+        let shuffle = db.last_shuffle(guild_id).unwrap().unwrap();
+        assert_eq!(shuffle.id, first);
+        assert_eq!(shuffle.teams, teams);
+        assert_eq!(shuffle.winner, None);
+
+        let ratings = HashMap::from([(user(1), 1010.0), (user(2), 1010.0), (user(3), 990.0)]);
+        assert!(db.record_result(guild_id, first, 1, &ratings).unwrap());
+        // A second result for the same shuffle changes nothing
+        let changed = HashMap::from([(user(1), 2000.0)]);
+        assert!(!db.record_result(guild_id, first, 0, &changed).unwrap());
+        // Shuffles without a result don't count
+        db.record_shuffle(guild_id, &teams, 200).unwrap();
+
+        assert_eq!(db.ratings(guild_id).unwrap(), ratings);
+        let records = db.records(guild_id).unwrap();
+        assert_eq!(records[&user(1)], Record { games: 1, wins: 0 });
+        assert_eq!(records[&user(3)], Record { games: 1, wins: 1 });
+        assert_eq!(db.last_shuffle(guild_id).unwrap().unwrap().winner, None);
+        assert!(db.records(GuildId::new(2)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn recent_teams_are_newest_first() {
+        let db = Database::open(":memory:").unwrap();
+        let guild_id = GuildId::new(1);
+        let first = vec![vec![user(1)], vec![user(2)]];
+        let second = vec![vec![user(2), user(3)], vec![user(1)]];
+        let third = vec![vec![user(3)], vec![user(1), user(2)]];
+        for (created_at, teams) in [&first, &second, &third].into_iter().enumerate() {
+            db.record_shuffle(guild_id, teams, created_at as i64)
+                .unwrap();
+        }
+        db.record_shuffle(GuildId::new(2), &first, 10).unwrap();
+
+        assert_eq!(db.recent_teams(guild_id, 2).unwrap(), vec![third, second]);
+        assert!(db.recent_teams(guild_id, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn tracks_team_channels() {
+        let db = Database::open(":memory:").unwrap();
+        let channel_id = ChannelId::new(5);
+
+        assert!(!db.is_team_channel(channel_id).unwrap());
+        db.add_team_channel(GuildId::new(1), channel_id).unwrap();
+        db.add_team_channel(GuildId::new(1), channel_id).unwrap();
+        assert!(db.is_team_channel(channel_id).unwrap());
+        assert_eq!(
+            db.team_channels().unwrap(),
+            vec![(GuildId::new(1), channel_id)]
+        );
+        db.remove_team_channel(channel_id).unwrap();
+        assert!(!db.is_team_channel(channel_id).unwrap());
+        assert!(db.team_channels().unwrap().is_empty());
+    }
+}
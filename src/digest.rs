@@ -0,0 +1,304 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, NaiveTime, TimeDelta, Utc, Weekday};
+use serenity::builder::{CreateEmbed, CreateMessage};
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+use tracing::{error, info};
+
+use crate::audit::{self, Outcome};
+use crate::db::{Announcement, Database, MediaKind};
+use crate::discord::DiscordOps;
+use crate::i18n::{tr, tr_args};
+use crate::metrics::METRICS;
+use crate::utils::color::Rgb;
+use crate::utils::markdown::FIELD_VALUE_LIMIT;
+
+/// Returns the first moment strictly after `now` that falls on `weekday` at `time` (UTC).
+pub fn next_occurrence(now: DateTime<Utc>, weekday: Weekday, time: NaiveTime) -> DateTime<Utc> {
+    let days_ahead = (7 + weekday.num_days_from_monday() as i64
+        - now.weekday().num_days_from_monday() as i64)
+        % 7;
+    let candidate = (now.date_naive() + TimeDelta::days(days_ahead))
+        .and_time(time)
+        .and_utc();
+    if candidate > now {
+        candidate
+    } else {
+        candidate + TimeDelta::weeks(1)
+    }
+}
+
+fn field_value(titles: &[&str], language: &str) -> String {
+    let mut value = String::new();
+    for (i, title) in titles.iter().enumerate() {
+        let line = format!("• {title}\n");
+        let remaining = titles.len() - i;
+        let more = tr_args(
+            language,
+            "digest.embed.more",
+            &[("count", &remaining.to_string())],
+        );
+        let length = value.chars().count() + line.chars().count() + more.chars().count();
+        if length > FIELD_VALUE_LIMIT {
+            value.push_str(&more);
+            return value;
+        }
+        value.push_str(&line);
+    }
+    value
+}
+
+/// The digest of `announcements` in `language`, `None` when there are none.
+pub fn build_embed(
+    announcements: &[Announcement],
+    color: Rgb,
+    language: &str,
+) -> Option<CreateEmbed> {
+    if announcements.is_empty() {
+        return None;
+    }
+
+    let mut embed = CreateEmbed::new()
+        .title(tr(language, "digest.embed.title"))
+        .description(tr(language, "digest.embed.description"))
+        .color(color);
+
+    for (kind, key) in [
+        (MediaKind::Movie, "digest.embed.movies"),
+        (MediaKind::Show, "digest.embed.shows"),
+        (MediaKind::Anime, "digest.embed.anime"),
+    ] {
+        let titles: Vec<&str> = announcements
+            .iter()
+            .filter(|announcement| announcement.kind == kind)
+            .map(|announcement| announcement.title.as_str())
+            .collect();
+        if !titles.is_empty() {
+            embed = embed.field(tr(language, key), field_value(&titles, language), false);
+        }
+    }
+
+    Some(embed)
+}
+
+/// Posts a digest of the past week's announcements in a guild, on behalf of `user_id`. Returns
+/// `Ok(false)` when there was nothing to post.
+pub async fn send_digest(
+    discord: &dyn DiscordOps,
+    db: &Database,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    user_id: UserId,
+    color: Rgb,
+    language: &str,
+) -> Result<bool, String> {
+    let since = (Utc::now() - TimeDelta::weeks(1)).timestamp();
+    let announcements = db.announcements_since(guild_id, since)?;
+    let Some(embed) = build_embed(&announcements, color, language) else {
+        return Ok(false);
+    };
+
+    let (result, outcome) = match discord
+        .send_message(channel_id, CreateMessage::new().embed(embed))
+        .await
+    {
+        Ok(link) => {
+            METRICS.digests_posted.inc();
+            (Ok(true), Outcome::Success(link))
+        }
+        Err(e) => (Err(format!("Cannot post digest: {e}")), Outcome::Failure(e)),
+    };
+    let entry = audit::entry(user_id, "Posted the weekly digest", outcome);
+    audit::log(discord, db, guild_id, vec![entry]).await;
+    result
+}
+
+/// Posts the digest in every guild with an announcements channel every week on `weekday` at
+/// `time` (UTC), forever. Done on behalf of `user_id`, the bot itself.
+pub async fn schedule(
+    discord: impl DiscordOps,
+    db: Database,
+    user_id: UserId,
+    weekday: Weekday,
+    time: NaiveTime,
+    color: Rgb,
+    language: String,
+) {
+    loop {
+        let now = Utc::now();
+        let next = next_occurrence(now, weekday, time);
+        info!(%next, "Next digest scheduled");
+        tokio::time::sleep((next - now).to_std().unwrap_or(Duration::from_secs(0))).await;
+
+        let guilds = match db.all_guild_settings() {
+            Ok(guilds) => guilds,
+            Err(e) => {
+                error!(error = %e, "Cannot read guild settings");
+                continue;
+            }
+        };
+        for settings in guilds {
+            let Some(channel_id) = settings.announcements_channel_id else {
+                continue;
+            };
+            let sent = send_digest(
+                &discord,
+                &db,
+                settings.guild_id,
+                channel_id,
+                user_id,
+                color,
+                &language,
+            )
+            .await;
+            match sent {
+                Ok(true) => info!(guild = %settings.guild_id, "Digest posted"),
+                Ok(false) => info!(
+                    guild = %settings.guild_id,
+                    "Nothing announced this week, skipping digest"
+                ),
+                Err(e) => error!(guild = %settings.guild_id, error = %e, "Cannot post digest"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::db::GuildSettings;
+    use crate::discord::fake::FakeDiscord;
+
+    const ANNOUNCEMENTS: u64 = 30;
+    const AUDIT: u64 = 31;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        // 2024-01-01 is a Monday
+        Utc.with_ymd_and_hms(2024, 1, day, hour, 0, 0).unwrap()
+    }
+
+    fn announcement(kind: MediaKind, title: &str) -> Announcement {
+        Announcement {
+            kind,
+            title: title.to_string(),
+        }
+    }
+
+    #[test]
+    fn next_occurrence_later_this_week() {
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+
+        assert_eq!(next_occurrence(at(1, 12), Weekday::Wed, nine), at(3, 9));
+    }
+
+    #[test]
+    fn next_occurrence_wraps_to_next_week() {
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+
+        assert_eq!(next_occurrence(at(5, 12), Weekday::Mon, nine), at(8, 9));
+    }
+
+    #[test]
+    fn next_occurrence_on_the_same_day() {
+        let nine = NaiveTime::from_hms_opt(9, 0, 0).unwrap();
+
+        assert_eq!(next_occurrence(at(1, 8), Weekday::Mon, nine), at(1, 9));
+        // Already passed, or right now, is next week
+        assert_eq!(next_occurrence(at(1, 12), Weekday::Mon, nine), at(8, 9));
+        assert_eq!(next_occurrence(at(1, 9), Weekday::Mon, nine), at(8, 9));
+    }
+
+    #[test]
+    fn field_values_stay_within_the_limit() {
+        let title = "A".repeat(100);
+        let titles = vec![title.as_str(); 20];
+
+        let value = field_value(&titles, "en-US");
+
+        assert!(value.chars().count() <= FIELD_VALUE_LIMIT);
+        assert_eq!(value.matches('•').count(), 9);
+        assert!(value.ends_with("… and 11 more"), "{value}");
+    }
+
+    #[test]
+    fn short_field_values_list_everything() {
+        assert_eq!(
+            field_value(&["Alien", "Aliens"], "en-US"),
+            "• Alien\n• Aliens\n"
+        );
+    }
+
+    #[test]
+    fn embeds_group_announcements_by_kind() {
+        assert!(build_embed(&[], (0, 0, 0), "en-US").is_none());
+
+        let announcements = [
+            announcement(MediaKind::Show, "Peter Pan"),
+            announcement(MediaKind::Movie, "Alien"),
+            announcement(MediaKind::Movie, "Aliens"),
+        ];
+        let embed = build_embed(&announcements, (0, 0, 0), "nl-NL").unwrap();
+
+        let embed = serde_json::to_value(embed).unwrap();
+        assert_eq!(embed["title"], "Nieuw op Jellyfin");
+        assert_eq!(embed["fields"][0]["name"], "Films");
+        assert_eq!(embed["fields"][0]["value"], "• Alien\n• Aliens\n");
+        assert_eq!(embed["fields"][1]["name"], "Series");
+        assert_eq!(embed["fields"].as_array().unwrap().len(), 2);
+    }
+
+    fn guild() -> (FakeDiscord, Database) {
+        let discord = FakeDiscord::default();
+        let db = Database::open(":memory:").unwrap();
+        db.save_guild_settings(&GuildSettings {
+            audit_channel_id: Some(ChannelId::new(AUDIT)),
+            ..GuildSettings::new(GuildId::new(1))
+        })
+        .unwrap();
+        (discord, db)
+    }
+
+    async fn send(discord: &FakeDiscord, db: &Database) -> Result<bool, String> {
+        send_digest(
+            discord,
+            db,
+            GuildId::new(1),
+            ChannelId::new(ANNOUNCEMENTS),
+            UserId::new(99),
+            (0, 0, 0),
+            "en-US",
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn posts_and_logs_the_digest() {
+        let (discord, db) = guild();
+        db.record_announcement(
+            GuildId::new(1),
+            MediaKind::Movie,
+            "Alien",
+            Utc::now().timestamp(),
+        )
+        .unwrap();
+
+        assert_eq!(send(&discord, &db).await, Ok(true));
+
+        let digest = discord.messages_in(ChannelId::new(ANNOUNCEMENTS));
+        assert_eq!(digest.len(), 1);
+        assert_eq!(digest[0]["embeds"][0]["fields"][0]["value"], "• Alien\n");
+        let audit = discord.messages_in(ChannelId::new(AUDIT));
+        assert_eq!(audit[0]["embeds"][0]["title"], "Posted the weekly digest");
+    }
+
+    #[tokio::test]
+    async fn skips_weeks_without_announcements() {
+        let (discord, db) = guild();
+
+        assert_eq!(send(&discord, &db).await, Ok(false));
+
+        assert!(discord.state.lock().unwrap().messages.is_empty());
+    }
+}
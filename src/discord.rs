@@ -0,0 +1,165 @@
+#[cfg(test)]
+pub mod fake;
+
+use std::sync::Arc;
+
+use serenity::async_trait;
+use serenity::builder::{CreateChannel, CreateMessage};
+use serenity::cache::Cache;
+use serenity::http::Http;
+use serenity::model::prelude::{ChannelId, ChannelType, GuildId, UserId};
+use serenity::prelude::Context;
+
+/// The parts of a guild channel the bot looks at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelInfo {
+    pub id: ChannelId,
+    pub name: String,
+    pub parent_id: Option<ChannelId>,
+}
+
+/// A member connected to a voice channel.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VoiceMember {
+    pub user_id: UserId,
+    pub name: String,
+}
+
+/// Everything the commands change on Discord, so they can run against a fake in tests.
+#[async_trait]
+pub trait DiscordOps: Send + Sync {
+    async fn channels(&self, guild_id: GuildId) -> Result<Vec<ChannelInfo>, String>;
+
+    async fn voice_members(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+    ) -> Result<Vec<VoiceMember>, String>;
+
+    async fn create_voice_channel(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+        category_id: ChannelId,
+    ) -> Result<ChannelId, String>;
+
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<(), String>;
+
+    async fn move_member(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        channel_id: ChannelId,
+    ) -> Result<(), String>;
+
+    /// Returns a link to the message.
+    async fn send_message(
+        &self,
+        channel_id: ChannelId,
+        message: CreateMessage,
+    ) -> Result<String, String>;
+}
+
+/// [`DiscordOps`] through the REST API, with voice states from the gateway cache.
+#[derive(Clone)]
+pub struct SerenityDiscord {
+    http: Arc<Http>,
+    cache: Arc<Cache>,
+}
+
+impl SerenityDiscord {
+    pub fn new(ctx: &Context) -> SerenityDiscord {
+        SerenityDiscord {
+            http: ctx.http.clone(),
+            cache: ctx.cache.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl DiscordOps for SerenityDiscord {
+    async fn channels(&self, guild_id: GuildId) -> Result<Vec<ChannelInfo>, String> {
+        let channels = guild_id
+            .channels(&self.http)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(channels
+            .into_values()
+            .map(|channel| ChannelInfo {
+                id: channel.id,
+                name: channel.name,
+                parent_id: channel.parent_id,
+            })
+            .collect())
+    }
+
+    async fn voice_members(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+    ) -> Result<Vec<VoiceMember>, String> {
+        let guild = self
+            .cache
+            .guild(guild_id)
+            .ok_or("Guild is not in the cache".to_string())?;
+        Ok(guild
+            .voice_states
+            .values()
+            .filter(|state| state.channel_id == Some(channel_id))
+            .filter_map(|state| guild.members.get(&state.user_id))
+            .map(|member| VoiceMember {
+                user_id: member.user.id,
+                name: member.display_name().to_string(),
+            })
+            .collect())
+    }
+
+    async fn create_voice_channel(
+        &self,
+        guild_id: GuildId,
+        name: &str,
+        category_id: ChannelId,
+    ) -> Result<ChannelId, String> {
+        let builder = CreateChannel::new(name)
+            .kind(ChannelType::Voice)
+            .category(category_id);
+        guild_id
+            .create_channel(&self.http, builder)
+            .await
+            .map(|channel| channel.id)
+            .map_err(|e| e.to_string())
+    }
+
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<(), String> {
+        channel_id
+            .delete(&self.http)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn move_member(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+        channel_id: ChannelId,
+    ) -> Result<(), String> {
+        guild_id
+            .move_member(&self.http, user_id, channel_id)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn send_message(
+        &self,
+        channel_id: ChannelId,
+        message: CreateMessage,
+    ) -> Result<String, String> {
+        channel_id
+            .send_message(&self.http, message)
+            .await
+            .map(|message| message.link())
+            .map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::Mutex;
+
+use serde_json::Value;
+use serenity::async_trait;
+use serenity::builder::CreateMessage;
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+
+use super::{ChannelInfo, DiscordOps, VoiceMember};
+
+/// In-memory guild that records everything done to it.
+#[derive(Default)]
+pub struct FakeDiscord {
+    pub state: Mutex<State>,
+}
+
+#[derive(Default)]
+pub struct State {
+    pub channels: Vec<ChannelInfo>,
+    /// Members connected to voice and the channel they are in
+    pub voice: Vec<(VoiceMember, ChannelId)>,
+    pub deleted_channels: Vec<ChannelId>,
+    pub created_channels: Vec<ChannelId>,
+    pub moves: Vec<(UserId, ChannelId)>,
+    /// Sent messages, serialized the way they would be sent to Discord
+    pub messages: Vec<(ChannelId, Value)>,
+    /// Makes sending messages fail, to test error handling
+    pub fail_messages: bool,
+    /// Members that cannot be moved, to test error handling
+    pub unmovable: Vec<UserId>,
+    /// Channels that cannot be deleted, to test error handling
+    pub undeletable: Vec<ChannelId>,
+    next_id: u64,
+}
+
+impl FakeDiscord {
+    pub fn add_channel(&self, id: u64, name: &str, parent_id: Option<u64>) {
+        self.state.lock().unwrap().channels.push(ChannelInfo {
+            id: ChannelId::new(id),
+            name: name.to_string(),
+            parent_id: parent_id.map(ChannelId::new),
+        });
+    }
+
+    pub fn connect(&self, user_id: u64, name: &str, channel_id: u64) {
+        self.state.lock().unwrap().voice.push((
+            VoiceMember {
+                user_id: UserId::new(user_id),
+                name: name.to_string(),
+            },
+            ChannelId::new(channel_id),
+        ));
+    }
+
+    /// The members in a voice channel.
+    pub fn members_in(&self, channel_id: ChannelId) -> Vec<UserId> {
+        self.state
+            .lock()
+            .unwrap()
+            .voice
+            .iter()
+            .filter(|(_, channel)| *channel == channel_id)
+            .map(|(member, _)| member.user_id)
+            .collect()
+    }
+
+    pub fn messages_in(&self, channel_id: ChannelId) -> Vec<Value> {
+        self.state
+            .lock()
+            .unwrap()
+            .messages
+            .iter()
+            .filter(|(channel, _)| *channel == channel_id)
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl DiscordOps for FakeDiscord {
+    async fn channels(&self, _guild_id: GuildId) -> Result<Vec<ChannelInfo>, String> {
+        Ok(self.state.lock().unwrap().channels.clone())
+    }
+
+    async fn voice_members(
+        &self,
+        _guild_id: GuildId,
+        channel_id: ChannelId,
+    ) -> Result<Vec<VoiceMember>, String> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .voice
+            .iter()
+            .filter(|(_, channel)| *channel == channel_id)
+            .map(|(member, _)| member.clone())
+            .collect())
+    }
+
+    async fn create_voice_channel(
+        &self,
+        _guild_id: GuildId,
+        name: &str,
+        category_id: ChannelId,
+    ) -> Result<ChannelId, String> {
+        let mut state = self.state.lock().unwrap();
+        // Far away from the ids tests pick themselves
+        state.next_id += 1;
+        let id = ChannelId::new(1_000_000 + state.next_id);
+        state.channels.push(ChannelInfo {
+            id,
+            name: name.to_string(),
+            parent_id: Some(category_id),
+        });
+        state.created_channels.push(id);
+        Ok(id)
+    }
+
+    async fn delete_channel(&self, channel_id: ChannelId) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if state.undeletable.contains(&channel_id) {
+            return Err(format!("Missing permissions to delete {channel_id}"));
+        }
+        let before = state.channels.len();
+        state.channels.retain(|channel| channel.id != channel_id);
+        if state.channels.len() == before {
+            return Err(format!("Unknown channel {channel_id}"));
+        }
+        state.deleted_channels.push(channel_id);
+        Ok(())
+    }
+
+    async fn move_member(
+        &self,
+        _guild_id: GuildId,
+        user_id: UserId,
+        channel_id: ChannelId,
+    ) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        if state.unmovable.contains(&user_id) {
+            return Err("Missing Permissions".to_string());
+        }
+        if !state
+            .channels
+            .iter()
+            .any(|channel| channel.id == channel_id)
+        {
+            return Err(format!("Unknown channel {channel_id}"));
+        }
+        let Some((_, channel)) = state
+            .voice
+            .iter_mut()
+            .find(|(member, _)| member.user_id == user_id)
+        else {
+            return Err(format!("{user_id} is not connected to voice"));
+        };
+        *channel = channel_id;
+        state.moves.push((user_id, channel_id));
+        Ok(())
+    }
+
+    async fn send_message(
+        &self,
+        channel_id: ChannelId,
+        message: CreateMessage,
+    ) -> Result<String, String> {
+        let mut state = self.state.lock().unwrap();
+        if state.fail_messages {
+            return Err("Missing Access".to_string());
+        }
+        state
+            .messages
+            .push((channel_id, serde_json::to_value(&message).unwrap()));
+        Ok(format!(
+            "https://discord.com/channels/@me/{channel_id}/{}",
+            state.messages.len()
+        ))
+    }
+}
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use serenity::model::prelude::UserId;
+
+use crate::db::DEFAULT_RATING;
+
+/// Most points a team can win or lose in a single game.
+const K: f64 = 32.0;
+
+/// Chance that a team with rating `a` beats a team with rating `b`.
+fn expected_score(a: f64, b: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((b - a) / 400.0))
+}
+
+/// Updates the ratings of everyone who played after `winner` (an index into `teams`) won.
+///
+/// Teams are rated by the average of their members. The winners play every other team, each of
+/// those games is worth a share of [`K`], so winning against three teams moves ratings as much as
+/// winning against one. Points won by the winners are lost by the losers.
+pub fn update(ratings: &mut HashMap<UserId, f64>, teams: &[Vec<UserId>], winner: usize) {
+    let team_ratings: Vec<f64> = teams
+        .iter()
+        .map(|team| {
+            let total: f64 = team
+                .iter()
+                .map(|user_id| ratings.get(user_id).copied().unwrap_or(DEFAULT_RATING))
+                .sum();
+            total / team.len().max(1) as f64
+        })
+        .collect();
+    let k = K / (teams.len() - 1).max(1) as f64;
+
+    let mut deltas = vec![0.0; teams.len()];
+    for (loser, loser_rating) in team_ratings.iter().enumerate() {
+        if loser == winner || teams[loser].is_empty() {
+            continue;
+        }
+        let delta = k * (1.0 - expected_score(team_ratings[winner], *loser_rating));
+        deltas[winner] += delta;
+        deltas[loser] -= delta;
+    }
+
+    for (team, delta) in teams.iter().zip(deltas) {
+        for user_id in team {
+            *ratings.entry(*user_id).or_insert(DEFAULT_RATING) += delta;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: u64) -> UserId {
+        UserId::new(id)
+    }
+
+    #[test]
+    fn even_teams_trade_half_of_k() {
+        let mut ratings = HashMap::new();
+        let teams = vec![vec![user(1), user(2)], vec![user(3), user(4)]];
+
+        update(&mut ratings, &teams, 0);
+
+        assert_eq!(ratings[&user(1)], DEFAULT_RATING + K / 2.0);
+        assert_eq!(ratings[&user(2)], DEFAULT_RATING + K / 2.0);
+        assert_eq!(ratings[&user(3)], DEFAULT_RATING - K / 2.0);
+    }
+
+    #[test]
+    fn upsets_move_ratings_more() {
+        let teams = vec![vec![user(1)], vec![user(2)]];
+        let strong = HashMap::from([(user(1), 1400.0), (user(2), 1000.0)]);
+
+        let mut expected_win = strong.clone();
+        update(&mut expected_win, &teams, 0);
+        let mut upset = strong.clone();
+        update(&mut upset, &teams, 1);
+
+        let expected_gain = expected_win[&user(1)] - 1400.0;
+        let upset_gain = upset[&user(2)] - 1000.0;
+        assert!(upset_gain > expected_gain);
+        assert!(upset_gain < K);
+    }
+
+    #[test]
+    fn team_averages_are_zero_sum() {
+        let mut ratings = HashMap::from([(user(1), 1200.0), (user(4), 900.0)]);
+        let teams = vec![
+            vec![user(1), user(2)],
+            vec![user(3)],
+            vec![user(4), user(5)],
+        ];
+        let average = |ratings: &HashMap<UserId, f64>, team: &[UserId]| {
+            team.iter()
+                .map(|user_id| ratings.get(user_id).copied().unwrap_or(DEFAULT_RATING))
+                .sum::<f64>()
+                / team.len() as f64
+        };
+        let before: f64 = teams.iter().map(|team| average(&ratings, team)).sum();
+
+        update(&mut ratings, &teams, 1);
+
+        let after: f64 = teams.iter().map(|team| average(&ratings, team)).sum();
+        assert!((after - before).abs() < 1e-9);
+        assert!(ratings[&user(3)] > DEFAULT_RATING);
+    }
+}
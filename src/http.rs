@@ -0,0 +1,80 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use serenity::gateway::{ConnectionStage, ShardManager};
+use tracing::{error, info};
+
+use crate::metrics::METRICS;
+
+/// Whether every shard is connected, and the highest heartbeat latency among them.
+async fn gateway_status(shard_manager: &ShardManager) -> (bool, Option<Duration>) {
+    let runners = shard_manager.runners.lock().await;
+    let connected = !runners.is_empty()
+        && runners
+            .values()
+            .all(|runner| runner.stage == ConnectionStage::Connected);
+    let latency = runners.values().filter_map(|runner| runner.latency).max();
+    (connected, latency)
+}
+
+async fn live() -> &'static str {
+    "ok"
+}
+
+async fn ready(State(shard_manager): State<Arc<ShardManager>>) -> impl IntoResponse {
+    let (connected, latency) = gateway_status(&shard_manager).await;
+    let status = if connected {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let latency = match latency {
+        Some(latency) => latency.as_millis().to_string(),
+        None => "null".to_string(),
+    };
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/json")],
+        format!(r#"{{"connected":{connected},"latency_ms":{latency}}}"#),
+    )
+}
+
+async fn metrics(State(shard_manager): State<Arc<ShardManager>>) -> impl IntoResponse {
+    let (connected, latency) = gateway_status(&shard_manager).await;
+    METRICS.gateway_connected.set(connected as i64);
+    METRICS
+        .gateway_latency
+        .set(latency.unwrap_or_default().as_secs_f64());
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        METRICS.render(),
+    )
+}
+
+/// Serves `/health/live`, `/health/ready` and `/metrics` on `address` until the process exits.
+pub async fn serve(address: SocketAddr, shard_manager: Arc<ShardManager>) {
+    let app = Router::new()
+        .route("/health/live", get(live))
+        .route("/health/ready", get(ready))
+        .route("/metrics", get(metrics))
+        .with_state(shard_manager);
+
+    let listener = match tokio::net::TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(%address, error = %e, "Cannot start HTTP server");
+            return;
+        }
+    };
+    info!(%address, "Serving health checks and metrics");
+    if let Err(e) = axum::serve(listener, app).await {
+        error!(error = %e, "HTTP server stopped");
+    }
+}
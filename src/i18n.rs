@@ -0,0 +1,542 @@
+use std::sync::Mutex;
+
+use serenity::builder::{CreateCommand, CreateCommandOption};
+use serenity::model::application::CommandOptionType;
+use tracing::error;
+
+/// Locale used for command registration and whenever a message is missing in the user's locale.
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+/// Discord locales with a catalogue, besides the default one.
+const LOCALES: &[(&str, &[(&str, &str)])] = &[("nl", NL)];
+
+const EN: &[(&str, &str)] = &[
+    ("common.not_implemented", "not implemented"),
+    ("common.denied", "You are not allowed to use this command"),
+    ("common.invalid_subcommand", "Invalid subcommand"),
+    ("common.invalid_command", "Invalid command"),
+    ("common.invalid_id", "Please provide a valid ID"),
+    ("common.invalid_number", "Please provide a valid number"),
+    ("common.guild_only", "This command can only be used in a server"),
+    ("common.not_configured", "This server has not been configured for this command yet"),
+    ("announce.description", "Post an announcement"),
+    ("announce.unknown_type", "Unknown type"),
+    ("announce.no_type", "No type defined"),
+    ("announce.collection_not_in_library", "None of the films in this collection are on Jellyfin"),
+    ("announce.jellyfin_not_configured", "Jellyfin is not configured, cannot check the library"),
+    ("announce.sent", "Announcement sent in {channel}"),
+    ("announce.embed.available", "{title} is now available on Jellyfin!"),
+    ("announce.embed.episode", "{show} {season} Episode {number}"),
+    ("announce.embed.also_known_as", "Also known as"),
+    ("announce.embed.powered_by", "Powered by {provider}"),
+    ("language.de", "German"),
+    ("language.en", "English"),
+    ("language.es", "Spanish"),
+    ("language.fr", "French"),
+    ("language.it", "Italian"),
+    ("language.ja", "Japanese"),
+    ("language.nl", "Dutch"),
+    ("language.pt", "Portuguese"),
+    ("announce.failed", "Cannot post announcement: {error}"),
+    ("announce.anilist.description", "Announce a new movie, serie or season on Jellyfin"),
+    ("announce.anilist.id.description", "Id of the item on AniList"),
+    ("announce.tmdb.description", "Announce a new movie, series, season, episode or collection on Jellyfin"),
+    ("announce.tmdb.id.description", "Id of the item on TMDB"),
+    ("announce.tmdb.season_number.description", "Season number"),
+    ("announce.tmdb.episode_number.description", "Episode number"),
+    ("announce.tmdb.in_library.description", "Only list the films that are on Jellyfin"),
+    ("announce.tmdb.spoiler_safe.description", "Hide the description behind a spoiler, leave out the still and finale titles"),
+    ("announce.tmdb.movie.description", "Announce a new movie on Jellyfin"),
+    ("announce.tmdb.tv_show.description", "Announce a new TV show on Jellyfin"),
+    ("announce.tmdb.season.description", "Announce a new season on Jellyfin"),
+    ("announce.tmdb.episode.description", "Announce a new episode on Jellyfin"),
+    ("announce.tmdb.collection.description", "Announce a new collection on Jellyfin"),
+    ("config.description", "View or change the settings of this server"),
+    ("config.show.description", "Show the current settings"),
+    ("config.set.description", "Change a setting"),
+    ("config.set.announcements_channel.description", "Channel where announcements and digests are posted"),
+    ("config.set.shuffle_category.description", "Category in which shuffle creates the team channels"),
+    ("config.set.lobby_channel.description", "Voice channel shuffle takes the members from"),
+    ("config.set.audit_channel.description", "Channel that receives a log of everything done through the bot"),
+    ("config.set.value.description", "New value"),
+    ("config.roles.description", "Choose which roles may use a command"),
+    ("config.roles.allow.description", "Allow a role to use a command"),
+    ("config.roles.disallow.description", "No longer allow a role to use a command"),
+    ("config.roles.command.description", "Command or subcommand"),
+    ("config.roles.role.description", "Role"),
+    ("config.roles.heading", "Roles"),
+    ("config.roles.default", "No roles set, every command requires its default permissions"),
+    ("config.roles.allowed", "{role} may now use /{command}"),
+    ("config.roles.disallowed", "{role} may no longer use /{command}"),
+    ("config.roles.not_allowed", "{role} was not allowed to use /{command}"),
+    ("config.unset.description", "Unset a setting"),
+    ("config.unset.key.description", "Setting to unset"),
+    ("config.not_set", "not set"),
+    ("config.updated", "{key} is now {value}"),
+    ("config.cleared", "{key} is no longer set"),
+    ("digest.description", "Weekly digest of everything new on Jellyfin"),
+    ("digest.now.description", "Post the weekly digest right away"),
+    ("digest.sent", "Digest sent in {channel}"),
+    ("digest.empty", "Nothing was announced in the past week"),
+    ("digest.embed.title", "What's new on Jellyfin"),
+    ("digest.embed.description", "Everything that was added in the past week"),
+    ("digest.embed.movies", "Movies"),
+    ("digest.embed.shows", "Shows"),
+    ("digest.embed.anime", "Anime"),
+    ("digest.embed.more", "… and {count} more"),
+    ("shuffle.description", "Shuffle users to different voice channels. Useful for playing against eachother in random teams"),
+    ("shuffle.n_teams.description", "Number of teams"),
+    ("shuffle.nobody_in_lobby", "There is nobody in the lobby"),
+    ("shuffle.shuffling", "Shuffling into {n_teams} teams"),
+    ("shuffle.team_size.description", "Number of members per team, instead of the number of teams"),
+    ("shuffle.format.description", "Exact team sizes like 3v3v2, instead of the number of teams"),
+    ("shuffle.one_layout", "Give either a number of teams, a team size or a format"),
+    ("shuffle.invalid_format", "{format} is not a valid format, give team sizes like 3v3v2"),
+    ("shuffle.too_few_members", "There are only {members} members in the lobby, too few for {n_teams} teams"),
+    ("shuffle.too_many_teams", "That would make {n_teams} teams, at most {max} are possible"),
+    ("shuffle.format_mismatch", "The format is for {needed} members, but there are {members} in the lobby"),
+    ("shuffle.mode.description", "How to split the lobby into teams"),
+    ("shuffle.mode.random", "Random"),
+    ("shuffle.mode.balanced", "Balanced by rating"),
+    ("shuffle.auto_regroup_after.description", "Minutes after which everyone returns to the lobby"),
+    ("shuffle.auto_regroup", "Everyone returns to the lobby in {minutes} minutes"),
+    ("shuffle.mode.captains", "Captains draft"),
+    ("shuffle.captains.description", "Captains for the draft as mentions, random members of the lobby when left out"),
+    ("shuffle.invalid_captains", "Mention the captains, like @captain1 @captain2"),
+    ("shuffle.captain_count", "Give {n_teams} captains, one for each team"),
+    ("shuffle.captain_not_in_lobby", "{captain} is not in the lobby"),
+    ("shuffle.captains_without_draft", "Captains can only be given in the captains draft mode"),
+    ("shuffle.draft.started", "The draft has started: {link}"),
+    ("shuffle.draft.cancelled", "The draft that was in progress is cancelled"),
+    ("shuffle.draft.title", "Captains draft"),
+    ("shuffle.draft.team", "**Team {n}**: {members}"),
+    ("shuffle.draft.turn", "{captain}, pick a member for your team"),
+    ("shuffle.draft.placeholder", "Pick a member"),
+    ("shuffle.draft.done", "The draft is complete, moving everyone to their team"),
+    ("shuffle.draft.not_your_turn", "It is {captain}'s turn to pick"),
+    ("shuffle.draft.expired", "This draft is over, start a new one with /shuffle"),
+    ("regroup.description", "Move everyone in the team channels back to the lobby"),
+    ("regroup.done", "Moved {members} members back to {lobby}"),
+    ("regroup.nothing", "There are no team channels to regroup"),
+    ("regroup.not_moved", "Could not move {members}, their channels were kept"),
+    ("regroup.not_deleted", "Could not delete {channels}, remove them by hand"),
+    ("rating.description", "Skill ratings used to balance shuffled teams"),
+    ("rating.show.description", "Show the rating of a member"),
+    ("rating.set.description", "Set the rating of a member"),
+    ("rating.member.description", "The member"),
+    ("rating.value.description", "New rating, members start at 1000"),
+    ("rating.current", "{member} has a rating of {rating}"),
+    ("rating.updated", "{member} now has a rating of {rating}"),
+    ("result.description", "Record which team won the last shuffle and update the ratings"),
+    ("result.winner.description", "Number of the winning team"),
+    ("result.no_shuffle", "Nobody has been shuffled into teams yet"),
+    ("result.already_recorded", "The result of the last shuffle was already recorded"),
+    ("result.invalid_team", "The last shuffle has no team {team}"),
+    ("result.recorded", "Team {team} won! New ratings:"),
+    ("leaderboard.description", "Rankings by rating, with win rates"),
+    ("leaderboard.heading", "Leaderboard"),
+    ("leaderboard.empty", "Nobody has a rating yet"),
+    ("leaderboard.entry", "{rank}. {member}: {rating}, won {wins} of {games} ({win_rate}%)"),
+    ("leaderboard.entry_unplayed", "{rank}. {member}: {rating}"),
+];
+
+const NL: &[(&str, &str)] = &[
+    ("common.not_implemented", "niet geïmplementeerd"),
+    ("common.denied", "Je mag dit commando niet gebruiken"),
+    ("common.invalid_subcommand", "Ongeldig subcommando"),
+    ("common.invalid_command", "Ongeldig commando"),
+    ("common.invalid_id", "Geef een geldig ID op"),
+    ("common.invalid_number", "Geef een geldig nummer op"),
+    ("common.guild_only", "Dit commando kan enkel in een server gebruikt worden"),
+    ("common.not_configured", "Deze server is nog niet ingesteld voor dit commando"),
+    ("announce.name", "aankondigen"),
+    ("announce.description", "Plaats een aankondiging"),
+    ("announce.unknown_type", "Onbekend type"),
+    ("announce.no_type", "Geen type opgegeven"),
+    ("announce.collection_not_in_library", "Geen enkele film uit deze collectie staat op Jellyfin"),
+    ("announce.jellyfin_not_configured", "Jellyfin is niet ingesteld, de bibliotheek kan niet nagekeken worden"),
+    ("announce.sent", "Aankondiging geplaatst in {channel}"),
+    ("announce.embed.available", "{title} is nu beschikbaar op Jellyfin!"),
+    ("announce.embed.episode", "{show} {season} Aflevering {number}"),
+    ("announce.embed.also_known_as", "Ook bekend als"),
+    ("announce.embed.powered_by", "Mogelijk gemaakt door {provider}"),
+    ("language.de", "Duits"),
+    ("language.en", "Engels"),
+    ("language.es", "Spaans"),
+    ("language.fr", "Frans"),
+    ("language.it", "Italiaans"),
+    ("language.ja", "Japans"),
+    ("language.nl", "Nederlands"),
+    ("language.pt", "Portugees"),
+    ("announce.failed", "Kan de aankondiging niet plaatsen: {error}"),
+    ("announce.anilist.description", "Kondig een nieuwe film, serie of nieuw seizoen aan op Jellyfin"),
+    ("announce.anilist.id.description", "Id van het item op AniList"),
+    ("announce.tmdb.description", "Kondig een nieuwe film, serie, seizoen, aflevering of collectie aan op Jellyfin"),
+    ("announce.tmdb.id.description", "Id van het item op TMDB"),
+    ("announce.tmdb.season_number.name", "seizoen"),
+    ("announce.tmdb.season_number.description", "Nummer van het seizoen"),
+    ("announce.tmdb.episode_number.name", "aflevering"),
+    ("announce.tmdb.episode_number.description", "Nummer van de aflevering"),
+    ("announce.tmdb.in_library.name", "in_bibliotheek"),
+    ("announce.tmdb.in_library.description", "Toon enkel de films die op Jellyfin staan"),
+    ("announce.tmdb.spoiler_safe.name", "spoilervrij"),
+    ("announce.tmdb.spoiler_safe.description", "Verberg de beschrijving achter een spoiler, laat de still en titels van finales weg"),
+    ("announce.tmdb.movie.name", "film"),
+    ("announce.tmdb.movie.description", "Kondig een nieuwe film aan op Jellyfin"),
+    ("announce.tmdb.tv_show.name", "serie"),
+    ("announce.tmdb.tv_show.description", "Kondig een nieuwe serie aan op Jellyfin"),
+    ("announce.tmdb.season.name", "seizoen"),
+    ("announce.tmdb.season.description", "Kondig een nieuw seizoen aan op Jellyfin"),
+    ("announce.tmdb.episode.name", "aflevering"),
+    ("announce.tmdb.episode.description", "Kondig een nieuwe aflevering aan op Jellyfin"),
+    ("announce.tmdb.collection.name", "collectie"),
+    ("announce.tmdb.collection.description", "Kondig een nieuwe collectie aan op Jellyfin"),
+    ("config.name", "instellingen"),
+    ("config.description", "Bekijk of wijzig de instellingen van deze server"),
+    ("config.show.name", "tonen"),
+    ("config.show.description", "Toon de huidige instellingen"),
+    ("config.set.name", "wijzigen"),
+    ("config.set.description", "Wijzig een instelling"),
+    ("config.set.announcements_channel.name", "aankondigingskanaal"),
+    ("config.set.announcements_channel.description", "Kanaal waarin aankondigingen en overzichten geplaatst worden"),
+    ("config.set.shuffle_category.name", "shuffle_categorie"),
+    ("config.set.shuffle_category.description", "Categorie waarin shuffle de teamkanalen aanmaakt"),
+    ("config.set.lobby_channel.name", "lobbykanaal"),
+    ("config.set.lobby_channel.description", "Spraakkanaal waaruit shuffle de leden haalt"),
+    ("config.set.audit_channel.name", "auditkanaal"),
+    ("config.set.audit_channel.description", "Kanaal dat een logboek krijgt van alles wat via de bot gebeurt"),
+    ("config.set.value.name", "waarde"),
+    ("config.set.value.description", "Nieuwe waarde"),
+    ("config.roles.name", "rollen"),
+    ("config.roles.description", "Kies welke rollen een commando mogen gebruiken"),
+    ("config.roles.allow.name", "toestaan"),
+    ("config.roles.allow.description", "Laat een rol een commando gebruiken"),
+    ("config.roles.disallow.name", "intrekken"),
+    ("config.roles.disallow.description", "Laat een rol een commando niet langer gebruiken"),
+    ("config.roles.command.name", "commando"),
+    ("config.roles.command.description", "Commando of subcommando"),
+    ("config.roles.role.name", "rol"),
+    ("config.roles.role.description", "Rol"),
+    ("config.roles.heading", "Rollen"),
+    ("config.roles.default", "Geen rollen ingesteld, elk commando vereist zijn standaardrechten"),
+    ("config.roles.allowed", "{role} mag nu /{command} gebruiken"),
+    ("config.roles.disallowed", "{role} mag /{command} niet langer gebruiken"),
+    ("config.roles.not_allowed", "{role} mocht /{command} niet gebruiken"),
+    ("config.unset.name", "wissen"),
+    ("config.unset.description", "Wis een instelling"),
+    ("config.unset.key.name", "instelling"),
+    ("config.unset.key.description", "Instelling om te wissen"),
+    ("config.not_set", "niet ingesteld"),
+    ("config.updated", "{key} is nu {value}"),
+    ("config.cleared", "{key} is niet langer ingesteld"),
+    ("digest.name", "overzicht"),
+    ("digest.description", "Wekelijks overzicht van alles wat nieuw is op Jellyfin"),
+    ("digest.now.name", "nu"),
+    ("digest.now.description", "Plaats het wekelijks overzicht meteen"),
+    ("digest.sent", "Overzicht geplaatst in {channel}"),
+    ("digest.empty", "Er werd de voorbije week niets aangekondigd"),
+    ("digest.embed.title", "Nieuw op Jellyfin"),
+    ("digest.embed.description", "Alles wat de voorbije week werd toegevoegd"),
+    ("digest.embed.movies", "Films"),
+    ("digest.embed.shows", "Series"),
+    ("digest.embed.anime", "Anime"),
+    ("digest.embed.more", "… en nog {count}"),
+    ("shuffle.description", "Verdeel gebruikers over spraakkanalen. Handig om in willekeurige teams tegen elkaar te spelen"),
+    ("shuffle.n_teams.name", "aantal_teams"),
+    ("shuffle.n_teams.description", "Aantal teams"),
+    ("shuffle.nobody_in_lobby", "Er is niemand in de lobby"),
+    ("shuffle.shuffling", "Verdelen in {n_teams} teams"),
+    ("shuffle.team_size.name", "teamgrootte"),
+    ("shuffle.team_size.description", "Aantal leden per team, in plaats van het aantal teams"),
+    ("shuffle.format.name", "formaat"),
+    ("shuffle.format.description", "Exacte teamgroottes zoals 3v3v2, in plaats van het aantal teams"),
+    ("shuffle.one_layout", "Geef een aantal teams, een teamgrootte of een formaat op"),
+    ("shuffle.invalid_format", "{format} is geen geldig formaat, geef teamgroottes op zoals 3v3v2"),
+    ("shuffle.too_few_members", "Er zijn maar {members} leden in de lobby, te weinig voor {n_teams} teams"),
+    ("shuffle.too_many_teams", "Dat zou {n_teams} teams geven, er kunnen er maximaal {max} zijn"),
+    ("shuffle.format_mismatch", "Het formaat is voor {needed} leden, maar er zijn er {members} in de lobby"),
+    ("shuffle.mode.name", "modus"),
+    ("shuffle.mode.description", "Hoe de lobby in teams verdeeld wordt"),
+    ("shuffle.mode.random", "Willekeurig"),
+    ("shuffle.mode.balanced", "Gebalanceerd op rating"),
+    ("shuffle.auto_regroup_after.name", "terug_na"),
+    ("shuffle.auto_regroup_after.description", "Minuten waarna iedereen teruggaat naar de lobby"),
+    ("shuffle.auto_regroup", "Iedereen gaat over {minutes} minuten terug naar de lobby"),
+    ("shuffle.mode.captains", "Kapiteins kiezen"),
+    ("shuffle.captains.name", "kapiteins"),
+    ("shuffle.captains.description", "Kapiteins als vermeldingen, willekeurige leden van de lobby indien leeg"),
+    ("shuffle.invalid_captains", "Vermeld de kapiteins, zoals @kapitein1 @kapitein2"),
+    ("shuffle.captain_count", "Geef {n_teams} kapiteins op, één per team"),
+    ("shuffle.captain_not_in_lobby", "{captain} is niet in de lobby"),
+    ("shuffle.captains_without_draft", "Kapiteins kun je alleen opgeven bij kapiteins kiezen"),
+    ("shuffle.draft.started", "Het kiezen is begonnen: {link}"),
+    ("shuffle.draft.cancelled", "Het kiezen dat bezig was is geannuleerd"),
+    ("shuffle.draft.title", "Kapiteins kiezen"),
+    ("shuffle.draft.team", "**Team {n}**: {members}"),
+    ("shuffle.draft.turn", "{captain}, kies een lid voor je team"),
+    ("shuffle.draft.placeholder", "Kies een lid"),
+    ("shuffle.draft.done", "Alle teams zijn gekozen, iedereen wordt naar zijn team verplaatst"),
+    ("shuffle.draft.not_your_turn", "{captain} is aan de beurt om te kiezen"),
+    ("shuffle.draft.expired", "Dit kiezen is voorbij, begin opnieuw met /shuffle"),
+    ("regroup.name", "hergroeperen"),
+    ("regroup.description", "Verplaats iedereen in de teamkanalen terug naar de lobby"),
+    ("regroup.done", "{members} leden teruggezet naar {lobby}"),
+    ("regroup.nothing", "Er zijn geen teamkanalen om te hergroeperen"),
+    ("regroup.not_moved", "Kon {members} niet verplaatsen, hun kanalen zijn behouden"),
+    ("regroup.not_deleted", "Kon {channels} niet verwijderen, verwijder ze zelf"),
+    ("rating.description", "Ratings om geshuffelde teams in evenwicht te brengen"),
+    ("rating.show.name", "tonen"),
+    ("rating.show.description", "Toon de rating van een lid"),
+    ("rating.set.name", "instellen"),
+    ("rating.set.description", "Stel de rating van een lid in"),
+    ("rating.member.name", "lid"),
+    ("rating.member.description", "Het lid"),
+    ("rating.value.name", "waarde"),
+    ("rating.value.description", "Nieuwe rating, leden beginnen op 1000"),
+    ("rating.current", "{member} heeft een rating van {rating}"),
+    ("rating.updated", "{member} heeft nu een rating van {rating}"),
+    ("result.name", "uitslag"),
+    ("result.description", "Leg vast welk team de laatste shuffle won en werk de ratings bij"),
+    ("result.winner.name", "winnaar"),
+    ("result.winner.description", "Nummer van het winnende team"),
+    ("result.no_shuffle", "Er is nog niemand in teams verdeeld"),
+    ("result.already_recorded", "De uitslag van de laatste shuffle is al vastgelegd"),
+    ("result.invalid_team", "De laatste shuffle heeft geen team {team}"),
+    ("result.recorded", "Team {team} heeft gewonnen! Nieuwe ratings:"),
+    ("leaderboard.name", "ranglijst"),
+    ("leaderboard.description", "Ranglijst op rating, met winstpercentages"),
+    ("leaderboard.heading", "Ranglijst"),
+    ("leaderboard.empty", "Nog niemand heeft een rating"),
+    ("leaderboard.entry", "{rank}. {member}: {rating}, {wins} van {games} gewonnen ({win_rate}%)"),
+    ("leaderboard.entry_unplayed", "{rank}. {member}: {rating}"),
+];
+
+fn lookup(catalogue: &[(&str, &'static str)], key: &str) -> Option<&'static str> {
+    catalogue
+        .iter()
+        .find(|(entry, _)| *entry == key)
+        .map(|(_, message)| *message)
+}
+
+fn catalogue(locale: &str) -> Option<&'static [(&'static str, &'static str)]> {
+    // Discord uses both plain languages ("nl") and regional variants ("en-US", "pt-BR")
+    let language = locale.split('-').next().unwrap_or(locale);
+    LOCALES
+        .iter()
+        .find(|(code, _)| *code == locale)
+        .or_else(|| LOCALES.iter().find(|(code, _)| *code == language))
+        .map(|(_, catalogue)| *catalogue)
+}
+
+/// Returns the message for `key` in `locale`, falling back to English and then to the key itself.
+pub fn tr(locale: &str, key: &str) -> &'static str {
+    catalogue(locale)
+        .and_then(|catalogue| lookup(catalogue, key))
+        .or_else(|| lookup(EN, key))
+        .unwrap_or_else(|| {
+            error!(key, "Missing message");
+            missing(key)
+        })
+}
+
+/// A missing key as message, leaked once per key so it can be returned like a catalogue message.
+fn missing(key: &str) -> &'static str {
+    static MISSING: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+    let mut missing = MISSING.lock().unwrap();
+    match missing.iter().find(|entry| **entry == key) {
+        Some(entry) => entry,
+        None => {
+            let entry: &'static str = Box::leak(key.to_string().into_boxed_str());
+            missing.push(entry);
+            entry
+        }
+    }
+}
+
+/// The name of `language`, a tag like `nl-NL`, in `locale`. `None` for languages without a name.
+pub fn language_name(locale: &str, language: &str) -> Option<&'static str> {
+    let code = language.split('-').next().unwrap_or(language);
+    let key = format!("language.{code}");
+    catalogue(locale)
+        .and_then(|catalogue| lookup(catalogue, &key))
+        .or_else(|| lookup(EN, &key))
+}
+
+/// Like [`tr`], replacing every `{name}` placeholder with its value.
+pub fn tr_args(locale: &str, key: &str, args: &[(&str, &str)]) -> String {
+    let mut message = tr(locale, key).to_string();
+    for (name, value) in args {
+        message = message.replace(&format!("{{{name}}}"), value);
+    }
+    message
+}
+
+/// Creates a command with the description for `key` and the name and description localizations
+/// of every supported locale.
+pub fn command(name: &str, key: &str) -> CreateCommand {
+    let mut command =
+        CreateCommand::new(name).description(tr(DEFAULT_LOCALE, &format!("{key}.description")));
+    for (locale, catalogue) in LOCALES {
+        if let Some(localized_name) = lookup(catalogue, &format!("{key}.name")) {
+            command = command.name_localized(*locale, localized_name);
+        }
+        if let Some(description) = lookup(catalogue, &format!("{key}.description")) {
+            command = command.description_localized(*locale, description);
+        }
+    }
+    command
+}
+
+/// Like [`command`], for an option or subcommand.
+pub fn option(kind: CommandOptionType, name: &str, key: &str) -> CreateCommandOption {
+    let mut option = CreateCommandOption::new(
+        kind,
+        name,
+        tr(DEFAULT_LOCALE, &format!("{key}.description")),
+    );
+    for (locale, catalogue) in LOCALES {
+        if let Some(localized_name) = lookup(catalogue, &format!("{key}.name")) {
+            option = option.name_localized(*locale, localized_name);
+        }
+        if let Some(description) = lookup(catalogue, &format!("{key}.description")) {
+            option = option.description_localized(*locale, description);
+        }
+    }
+    option
+}
+
+/// Adds a string choice to an option, named by the message for `key` in every supported locale.
+pub fn string_choice(option: CreateCommandOption, key: &str, value: &str) -> CreateCommandOption {
+    let localizations = LOCALES
+        .iter()
+        .filter_map(|(locale, catalogue)| lookup(catalogue, key).map(|name| (*locale, name)));
+    option.add_string_choice_localized(tr(DEFAULT_LOCALE, key), value, localizations)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use regex::Regex;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::commands::COMMANDS;
+
+    /// Adds the name and localized names of a command or option to `names`, failing on any name
+    /// that is already in there.
+    fn add_names(names: &mut HashSet<(String, String)>, registered: &Value) {
+        let name = registered["name"].as_str().unwrap().to_string();
+        assert!(
+            names.insert((DEFAULT_LOCALE.to_string(), name.clone())),
+            "{name} is used twice"
+        );
+        for (locale, localized) in registered["name_localizations"]
+            .as_object()
+            .into_iter()
+            .flatten()
+        {
+            let localized = localized.as_str().unwrap().to_string();
+            assert!(
+                names.insert((locale.clone(), localized.clone())),
+                "{localized} is used twice in {locale}"
+            );
+        }
+    }
+
+    /// Fails on option names used twice at the same level, and on messages that were missing.
+    fn check_options(registered: &Value) {
+        let regex_key = Regex::new(r"^[a-z_]+(\.[a-z_]+)+$").unwrap();
+        for (field, value) in registered.as_object().into_iter().flatten() {
+            if let Some(text) = value.as_str() {
+                assert!(
+                    !regex_key.is_match(text),
+                    "Missing message {text} for {field}"
+                );
+            }
+        }
+        for choice in registered["choices"].as_array().into_iter().flatten() {
+            check_options(choice);
+        }
+
+        let mut names = HashSet::new();
+        for option in registered["options"].as_array().into_iter().flatten() {
+            add_names(&mut names, option);
+            check_options(option);
+        }
+    }
+
+    #[test]
+    fn every_command_registers() {
+        let mut names = HashSet::new();
+        for command in COMMANDS {
+            let registered = serde_json::to_value(command.register()).unwrap();
+            add_names(&mut names, &registered);
+            check_options(&registered);
+        }
+    }
+
+    #[test]
+    fn every_message_has_an_english_version() {
+        for (locale, catalogue) in LOCALES {
+            for (key, _) in *catalogue {
+                // Localized names are only needed where they differ from the English name
+                if key.ends_with(".name") {
+                    continue;
+                }
+                assert!(
+                    lookup(EN, key).is_some(),
+                    "{key} of {locale} is missing in EN"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn regional_locales_use_their_language() {
+        assert_eq!(
+            tr("nl-BE", "common.denied"),
+            "Je mag dit commando niet gebruiken"
+        );
+        assert_eq!(
+            tr("nl", "common.denied"),
+            "Je mag dit commando niet gebruiken"
+        );
+    }
+
+    #[test]
+    fn unknown_locales_use_english() {
+        assert_eq!(
+            tr("xx-YY", "common.denied"),
+            "You are not allowed to use this command"
+        );
+        assert_eq!(
+            tr("en-GB", "common.denied"),
+            "You are not allowed to use this command"
+        );
+    }
+
+    #[test]
+    fn missing_messages_fall_back_to_the_key() {
+        assert_eq!(tr("nl", "common.missing"), "common.missing");
+        assert_eq!(
+            tr_args("en-US", "common.missing", &[("name", "x")]),
+            "common.missing"
+        );
+    }
+
+    #[test]
+    fn names_languages_in_the_locale() {
+        assert_eq!(language_name("en-US", "nl-NL"), Some("Dutch"));
+        assert_eq!(language_name("nl", "en-US"), Some("Engels"));
+        assert_eq!(language_name("en-US", "xx"), None);
+    }
+
+    #[test]
+    fn replaces_placeholders() {
+        assert_eq!(
+            tr_args("en-US", "announce.sent", &[("channel", "#news")]),
+            "Announcement sent in #news"
+        );
+    }
+}
@@ -0,0 +1,12 @@
+use tracing_subscriber::EnvFilter;
+
+use crate::config::LogFormat;
+
+/// Installs the global subscriber, `filter` was already validated with the config.
+pub fn init(filter: &str, format: LogFormat) {
+    let builder = tracing_subscriber::fmt().with_env_filter(EnvFilter::new(filter));
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().with_current_span(true).init(),
+    }
+}
@@ -1,124 +1,361 @@
 mod api;
+mod audit;
 mod commands;
+mod config;
+mod db;
+mod digest;
+mod discord;
+mod elo;
+mod http;
+mod i18n;
+mod logging;
+mod metrics;
+mod team_channels;
 mod utils;
 
-use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use chrono::{NaiveTime, Weekday};
 
 use serenity::async_trait;
-use serenity::builder::{CreateInteractionResponse, CreateInteractionResponseMessage};
-use serenity::model::application::Interaction;
+use serenity::builder::{
+    CreateAutocompleteResponse, CreateInteractionResponse, CreateInteractionResponseMessage,
+};
+use serenity::model::application::{
+    Command, CommandInteraction, ComponentInteraction, Interaction,
+};
 use serenity::model::gateway::Ready;
-use serenity::model::prelude::{ChannelId, GuildId, UserId};
+use serenity::model::prelude::{GuildId, UserId};
+use serenity::model::voice::VoiceState;
 use serenity::prelude::*;
+use tracing::{debug, error, field, info, info_span, warn, Instrument, Span};
+
+use crate::metrics::METRICS;
+use crate::utils::color::Theme;
 
 pub struct Handler {
-    guild_id: GuildId,
+    /// Guild configured through the environment before multi-guild support, its guild-scoped
+    /// commands are removed in favour of the global ones
+    legacy_guild_id: Option<GuildId>,
     admin_user_id: UserId,
-    jellyfin_announcements_channel_id: ChannelId,
-    shuffle_category_id: ChannelId,
-    lobby_channel_id: ChannelId,
+    db: db::Database,
+    tmdb: api::tmdb::Client,
+    anilist: api::anilist::Client,
+    /// Only set when Jellyfin is configured
+    jellyfin: Option<api::jellyfin::Client>,
+    digest_weekday: Weekday,
+    digest_time: NaiveTime,
+    digest_scheduled: AtomicBool,
+    poster_colors: utils::palette::PosterColors,
+    theme: Theme,
+    spoiler_safe_default: bool,
+    language: String,
+    secondary_language: Option<String>,
+    /// How long team channels are kept once they are empty, kept forever when unset
+    team_channel_cleanup: Option<Duration>,
+    /// Number of recent shuffles whose teammates are kept apart if possible
+    shuffle_history: usize,
+    pending_cleanups: team_channels::PendingCleanups,
+    drafts: commands::shuffle::Drafts,
 }
 
-#[async_trait]
-impl EventHandler for Handler {
-    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        if let Interaction::Command(command) = interaction {
-            println!(
-                "Received command interaction: {:#?} from {:#?}",
-                command.data.name, command.user.name
+impl Handler {
+    async fn handle_command(&self, ctx: &Context, command: &CommandInteraction) {
+        let started = Instant::now();
+        debug!(user_name = %command.user.name, "Received command");
+
+        let result = match commands::find(&command.data.name) {
+            Some(slash_command) => {
+                let permissions =
+                    slash_command.subcommand_permissions(&commands::command_path(command));
+                match commands::check_access(command, self, permissions) {
+                    Ok(()) => slash_command.run(command, self, ctx).await,
+                    Err(e) => Err(e),
+                }
+            }
+            None => Err(i18n::tr(&command.locale, "common.not_implemented").to_string()),
+        };
+        let (response_message, outcome) = match &result {
+            Ok(message) => (message.as_str(), "success"),
+            Err(message) => (message.as_str(), "failure"),
+        };
+        METRICS
+            .commands
+            .with_label_values(&[command.data.name.as_str(), outcome])
+            .inc();
+
+        let data = CreateInteractionResponseMessage::new()
+            .content(response_message)
+            .ephemeral(true);
+        let builder = CreateInteractionResponse::Message(data);
+
+        if let Err(why) = command.create_response(&ctx.http, builder).await {
+            warn!(error = %why, "Cannot respond to slash command");
+        }
+        Span::current().record("latency_ms", started.elapsed().as_millis() as u64);
+        info!(outcome, reply = %response_message, "Handled command");
+
+        if let Some(guild_id) = command.guild_id {
+            let entry = audit::entry(
+                command.user.id,
+                &format!("/{}", commands::command_path(command).join(" ")),
+                match result {
+                    Ok(message) => audit::Outcome::Success(message),
+                    Err(message) => audit::Outcome::Failure(message),
+                },
             );
+            audit::log(
+                &discord::SerenityDiscord::new(ctx),
+                &self.db,
+                guild_id,
+                vec![entry],
+            )
+            .await;
+        }
+    }
+
+    async fn handle_autocomplete(&self, ctx: &Context, command: &CommandInteraction) {
+        let Some(slash_command) = commands::find(&command.data.name) else {
+            return;
+        };
+        let choices = slash_command.autocomplete(command, self, ctx).await;
+        let builder = CreateInteractionResponse::Autocomplete(
+            CreateAutocompleteResponse::new().set_choices(choices),
+        );
+        if let Err(why) = command.create_response(&ctx.http, builder).await {
+            warn!(error = %why, "Cannot respond to autocomplete");
+        }
+    }
 
-            let response_message = match command.data.name.as_str() {
-                "announce" => commands::announce::run(&command, &self, &ctx).await,
-                "shuffle" => commands::shuffle::run(&command, &self, &ctx).await,
-                _ => "not implemented".to_string(),
-            };
+    async fn handle_component(&self, ctx: &Context, component: &ComponentInteraction) {
+        let name = component
+            .data
+            .custom_id
+            .split_once(':')
+            .map_or(component.data.custom_id.as_str(), |(name, _)| name);
+        let result = match commands::find(name) {
+            Some(slash_command) => slash_command.component(component, self, ctx).await,
+            None => Err(i18n::tr(&component.locale, "common.not_implemented").to_string()),
+        };
 
+        if let Err(message) = result {
+            info!(reply = %message, "Component interaction failed");
             let data = CreateInteractionResponseMessage::new()
-                .content(response_message)
+                .content(message)
                 .ephemeral(true);
             let builder = CreateInteractionResponse::Message(data);
+            if let Err(why) = component.create_response(&ctx.http, builder).await {
+                warn!(error = %why, "Cannot respond to component interaction");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl Handler {
+    /// A handler with an in-memory database, talking to TMDB and AniList at `base_url`.
+    fn for_tests(base_url: &str) -> Handler {
+        Handler {
+            legacy_guild_id: None,
+            admin_user_id: UserId::new(99),
+            db: db::Database::open(":memory:").unwrap(),
+            tmdb: api::tmdb::Client::new(base_url.to_string(), "token".to_string()),
+            anilist: api::anilist::Client::new(base_url.to_string()),
+            jellyfin: None,
+            digest_weekday: Weekday::Mon,
+            digest_time: NaiveTime::MIN,
+            digest_scheduled: AtomicBool::new(false),
+            poster_colors: utils::palette::PosterColors::default(),
+            theme: Theme::default(),
+            spoiler_safe_default: false,
+            language: "en-US".to_string(),
+            secondary_language: None,
+            team_channel_cleanup: None,
+            shuffle_history: 0,
+            pending_cleanups: team_channels::PendingCleanups::default(),
+            drafts: commands::shuffle::Drafts::default(),
+        }
+    }
+}
 
-            if let Err(why) = command.create_response(&ctx.http, builder).await {
-                println!("Cannot respond to slash command: {}", why);
+#[async_trait]
+impl EventHandler for Handler {
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::Command(command) => {
+                let span = info_span!(
+                    "interaction",
+                    command = %commands::command_path(&command).join(" "),
+                    user = %command.user.id,
+                    guild = command.guild_id.map(|guild_id| guild_id.get()),
+                    latency_ms = field::Empty,
+                );
+                self.handle_command(&ctx, &command).instrument(span).await;
             }
+            Interaction::Autocomplete(command) => self.handle_autocomplete(&ctx, &command).await,
+            Interaction::Component(component) => {
+                let span = info_span!(
+                    "interaction",
+                    component = %component.data.custom_id,
+                    user = %component.user.id,
+                    guild = component.guild_id.map(|guild_id| guild_id.get()),
+                );
+                self.handle_component(&ctx, &component)
+                    .instrument(span)
+                    .await;
+            }
+            _ => {}
         }
     }
 
-    async fn ready(&self, ctx: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
-        let commands = self
-            .guild_id
-            .set_commands(
-                &ctx.http,
-                vec![
-                    commands::announce::register(),
-                    commands::shuffle::register(),
-                ],
+    async fn voice_state_update(&self, ctx: Context, old: Option<VoiceState>, new: VoiceState) {
+        let Some(grace) = self.team_channel_cleanup else {
+            return;
+        };
+        // Only leaving a channel can empty it
+        let (Some(guild_id), Some(left)) = (new.guild_id, old.and_then(|old| old.channel_id))
+        else {
+            return;
+        };
+        if new.channel_id == Some(left) {
+            return;
+        }
+
+        let discord = discord::SerenityDiscord::new(&ctx);
+        let db = self.db.clone();
+        let pending = self.pending_cleanups.clone();
+        let user_id = ctx.cache.current_user().id;
+        tokio::spawn(async move {
+            team_channels::delete_when_empty(
+                &discord, &db, &pending, user_id, guild_id, left, grace,
             )
-            .await;
+            .await
+        });
+    }
+
+    // Sweeps on cache ready rather than ready, voice states are only known once guilds are cached
+    async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
+        let Some(grace) = self.team_channel_cleanup else {
+            return;
+        };
+        let discord = discord::SerenityDiscord::new(&ctx);
+        let db = self.db.clone();
+        let user_id = ctx.cache.current_user().id;
+        tokio::spawn(async move { team_channels::sweep(&discord, &db, user_id, grace).await });
+    }
+
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        info!(user = %ready.user.name, "Connected");
+        let commands = Command::set_global_commands(
+            &ctx.http,
+            commands::COMMANDS
+                .iter()
+                .map(|command| {
+                    command
+                        .register()
+                        .default_member_permissions(command.permissions())
+                })
+                .collect(),
+        )
+        .await;
+
+        match commands {
+            Ok(commands) => info!(count = commands.len(), "Registered global commands"),
+            Err(e) => error!(error = %e, "Cannot register global commands"),
+        }
+
+        if let Some(guild_id) = self.legacy_guild_id {
+            if let Err(why) = guild_id.set_commands(&ctx.http, vec![]).await {
+                warn!(guild = %guild_id, error = %why, "Cannot remove guild commands");
+            }
+        }
 
-        println!("I created the following slash command: {:#?}", commands);
+        // Ready fires again after a reconnect, only start the digest schedule once
+        if !self.digest_scheduled.swap(true, Ordering::SeqCst) {
+            tokio::spawn(digest::schedule(
+                discord::SerenityDiscord::new(&ctx),
+                self.db.clone(),
+                ready.user.id,
+                self.digest_weekday,
+                self.digest_time,
+                self.theme.digest,
+                self.language.clone(),
+            ));
+        }
     }
 }
 
 #[tokio::main]
 async fn main() {
-    // Configure the client with your Discord bot token in the environment.
-    let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
-
-    let guild_id = GuildId::new(
-        env::var("GUILD_ID")
-            .expect("Expected GUILD_ID in environment")
-            .parse()
-            .expect("GUILD_ID must be an integer"),
-    );
-
-    let admin_user_id = UserId::new(
-        env::var("ADMIN_USER_ID")
-            .expect("Expected ADMIN_USER_ID in environment")
-            .parse()
-            .expect("ADMIN_USER_ID must be an integer"),
-    );
-
-    let jellyfin_announcements_channel_id = ChannelId::new(
-        env::var("JELLYFIN_ANNOUNCEMENTS_CHANNEL_ID")
-            .expect("Expected JELLYFIN_ANNOUNCEMENTS_CHANNEL_ID in environment")
-            .parse()
-            .expect("JELLYFIN_ANNOUNCEMENTS_CHANNEL_ID must be an integer"),
-    );
-
-    let shuffle_category_id = ChannelId::new(
-        env::var("SHUFFLE_CATEGORY_ID")
-            .expect("Expected SHUFFLE_CATEGORY_ID in environment")
-            .parse()
-            .expect("SHUFFLE_CATEGORY_ID must be an integer"),
-    );
-
-    let lobby_channel_id = ChannelId::new(
-        env::var("LOBBY_CHANNEL_ID")
-            .expect("Expected LOBBY_CHANNEL_ID in environment")
-            .parse()
-            .expect("LOBBY_CHANNEL_ID must be an integer"),
-    );
+    let config = match config::Config::load() {
+        Ok(config) => config,
+        Err(errors) => {
+            eprintln!("Invalid configuration:");
+            for error in errors {
+                eprintln!("  - {error}");
+            }
+            std::process::exit(1);
+        }
+    };
+
+    logging::init(&config.log_filter, config.log_format);
+
+    let db = db::Database::open(&config.database_path).expect("Cannot open database");
+
+    // Deployments from before multi-guild support configure their single guild in the
+    // config, carry that over to the database without overwriting later changes
+    if let Some(legacy) = &config.legacy_guild {
+        let mut settings = db
+            .guild_settings(legacy.guild_id)
+            .expect("Cannot read guild settings");
+        settings.announcements_channel_id = settings
+            .announcements_channel_id
+            .or(legacy.announcements_channel_id);
+        settings.shuffle_category_id = settings.shuffle_category_id.or(legacy.shuffle_category_id);
+        settings.lobby_channel_id = settings.lobby_channel_id.or(legacy.lobby_channel_id);
+        db.save_guild_settings(&settings)
+            .expect("Cannot save guild settings");
+        db.adopt_unassigned_announcements(legacy.guild_id)
+            .expect("Cannot assign announcements to guild");
+    }
 
     // Build our client.
     let mut client = Client::builder(
-        token,
+        &config.discord_token,
         GatewayIntents::GUILDS | GatewayIntents::GUILD_VOICE_STATES,
     )
     .event_handler(Handler {
-        guild_id,
-        admin_user_id,
-        jellyfin_announcements_channel_id,
-        shuffle_category_id,
-        lobby_channel_id,
+        legacy_guild_id: config.legacy_guild.map(|legacy| legacy.guild_id),
+        admin_user_id: config.admin_user_id,
+        db,
+        tmdb: api::tmdb::Client::new(api::tmdb::BASE_URL.to_string(), config.tmdb_token),
+        anilist: api::anilist::Client::new(api::anilist::BASE_URL.to_string()),
+        jellyfin: config
+            .jellyfin
+            .map(|jellyfin| api::jellyfin::Client::new(jellyfin.url, jellyfin.api_key)),
+        digest_weekday: config.digest_weekday,
+        digest_time: config.digest_time,
+        digest_scheduled: AtomicBool::new(false),
+        poster_colors: utils::palette::PosterColors::default(),
+        theme: config.theme,
+        spoiler_safe_default: config.spoiler_safe,
+        language: config.language,
+        secondary_language: config.secondary_language,
+        team_channel_cleanup: config.team_channel_cleanup,
+        shuffle_history: config.shuffle_history,
+        pending_cleanups: team_channels::PendingCleanups::default(),
+        drafts: commands::shuffle::Drafts::default(),
     })
     .await
     .expect("Error creating client");
 
     let shard_manager = client.shard_manager.clone();
 
+    if let Some(address) = config.http_address {
+        tokio::spawn(http::serve(address, client.shard_manager.clone()));
+    }
+
     // Handle gracefull shutdown on sigint (ctrl+c) and sigterm
     tokio::spawn(async move {
         let mut sigint =
@@ -127,8 +364,8 @@ async fn main() {
             tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()).unwrap();
 
         tokio::select! {
-            _ = sigint.recv() => println!("SIGINT"),
-            _ = sigterm.recv() => println!("SIGTERM"),
+            _ = sigint.recv() => info!("Received SIGINT, shutting down"),
+            _ = sigterm.recv() => info!("Received SIGTERM, shutting down"),
         }
         shard_manager.shutdown_all().await;
     });
@@ -138,6 +375,6 @@ async fn main() {
     // Shards will automatically attempt to reconnect, and will perform
     // exponential backoff until it reconnects.
     if let Err(why) = client.start().await {
-        println!("Client error: {:?}", why);
+        error!(error = ?why, "Client error");
     }
 }
@@ -0,0 +1,132 @@
+use std::future::Future;
+use std::sync::LazyLock;
+use std::time::Instant;
+
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+    /// Labelled with the command name and `success` or `failure`
+    pub commands: IntCounterVec,
+    /// Labelled with the provider, like `tmdb`
+    pub provider_request_duration: HistogramVec,
+    pub provider_request_errors: IntCounterVec,
+    /// Labelled with the kind of media
+    pub announcements_posted: IntCounterVec,
+    pub digests_posted: IntCounter,
+    pub members_moved: IntCounter,
+    /// Only updated when scraped
+    pub gateway_connected: IntGauge,
+    pub gateway_latency: Gauge,
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        let commands = IntCounterVec::new(
+            Opts::new("bot_commands_total", "Slash commands handled"),
+            &["command", "outcome"],
+        )
+        .unwrap();
+        let provider_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "bot_provider_request_duration_seconds",
+                "Duration of requests to metadata providers",
+            ),
+            &["provider"],
+        )
+        .unwrap();
+        let provider_request_errors = IntCounterVec::new(
+            Opts::new(
+                "bot_provider_request_errors_total",
+                "Failed requests to metadata providers",
+            ),
+            &["provider"],
+        )
+        .unwrap();
+        let announcements_posted = IntCounterVec::new(
+            Opts::new("bot_announcements_posted_total", "Announcements posted"),
+            &["kind"],
+        )
+        .unwrap();
+        let digests_posted =
+            IntCounter::new("bot_digests_posted_total", "Weekly digests posted").unwrap();
+        let members_moved =
+            IntCounter::new("bot_members_moved_total", "Members moved by shuffles").unwrap();
+        let gateway_connected = IntGauge::new(
+            "bot_gateway_connected",
+            "Whether every shard is connected to the gateway",
+        )
+        .unwrap();
+        let gateway_latency = Gauge::new(
+            "bot_gateway_latency_seconds",
+            "Highest heartbeat latency of the shards",
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(commands.clone())).unwrap();
+        registry
+            .register(Box::new(provider_request_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(provider_request_errors.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(announcements_posted.clone()))
+            .unwrap();
+        registry.register(Box::new(digests_posted.clone())).unwrap();
+        registry.register(Box::new(members_moved.clone())).unwrap();
+        registry
+            .register(Box::new(gateway_connected.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(gateway_latency.clone()))
+            .unwrap();
+
+        Metrics {
+            registry,
+            commands,
+            provider_request_duration,
+            provider_request_errors,
+            announcements_posted,
+            digests_posted,
+            members_moved,
+            gateway_connected,
+            gateway_latency,
+        }
+    }
+
+    /// All metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+/// Runs a request to `provider`, recording how long it took and whether it failed.
+pub async fn provider_request<T>(
+    provider: &str,
+    request: impl Future<Output = Result<T, String>>,
+) -> Result<T, String> {
+    let started = Instant::now();
+    let result = request.await;
+    METRICS
+        .provider_request_duration
+        .with_label_values(&[provider])
+        .observe(started.elapsed().as_secs_f64());
+    if result.is_err() {
+        METRICS
+            .provider_request_errors
+            .with_label_values(&[provider])
+            .inc();
+    }
+    result
+}
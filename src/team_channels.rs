@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+use tracing::{error, info};
+
+use crate::audit::{self, Outcome};
+use crate::db::Database;
+use crate::discord::DiscordOps;
+
+/// The latest cleanup scheduled for each team channel, older ones give way to it so a channel is
+/// only deleted after staying empty for the whole grace period.
+#[derive(Clone, Default)]
+pub struct PendingCleanups {
+    inner: Arc<Mutex<Pending>>,
+}
+
+#[derive(Default)]
+struct Pending {
+    next_token: u64,
+    latest: HashMap<ChannelId, u64>,
+}
+
+impl PendingCleanups {
+    fn schedule(&self, channel_id: ChannelId) -> u64 {
+        let mut pending = self.inner.lock().unwrap();
+        pending.next_token += 1;
+        let token = pending.next_token;
+        pending.latest.insert(channel_id, token);
+        token
+    }
+
+    /// Whether `token` is still the latest cleanup of the channel, which it then no longer is.
+    fn take(&self, channel_id: ChannelId, token: u64) -> bool {
+        let mut pending = self.inner.lock().unwrap();
+        if pending.latest.get(&channel_id) != Some(&token) {
+            return false;
+        }
+        pending.latest.remove(&channel_id);
+        true
+    }
+}
+
+/// Deletes a team channel once it has been empty for `grace`, done on behalf of `user_id`.
+///
+/// Started whenever someone leaves a channel, channels that are not team channels are ignored.
+pub async fn delete_when_empty(
+    discord: &dyn DiscordOps,
+    db: &Database,
+    pending: &PendingCleanups,
+    user_id: UserId,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    grace: Duration,
+) {
+    match db.is_team_channel(channel_id) {
+        Ok(true) => {}
+        Ok(false) => return,
+        Err(e) => {
+            error!(error = %e, "Cannot look up team channel");
+            return;
+        }
+    }
+
+    let token = pending.schedule(channel_id);
+    tokio::time::sleep(grace).await;
+    if !pending.take(channel_id, token) {
+        return;
+    }
+    delete_if_empty(discord, db, user_id, guild_id, channel_id).await;
+}
+
+async fn delete_if_empty(
+    discord: &dyn DiscordOps,
+    db: &Database,
+    user_id: UserId,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) {
+    match discord.voice_members(guild_id, channel_id).await {
+        Ok(members) if members.is_empty() => {}
+        Ok(_) => return,
+        Err(e) => {
+            error!(error = %e, "Cannot check who is in a team channel");
+            return;
+        }
+    }
+    // Gone already when a shuffle or regroup got to it first
+    if !db.is_team_channel(channel_id).unwrap_or(false) {
+        return;
+    }
+    let channel = match discord.channels(guild_id).await {
+        Ok(channels) => channels
+            .into_iter()
+            .find(|channel| channel.id == channel_id),
+        Err(e) => {
+            error!(error = %e, "Cannot look up team channel");
+            return;
+        }
+    };
+    // Someone deleted it by hand
+    let Some(channel) = channel else {
+        if let Err(e) = db.remove_team_channel(channel_id) {
+            error!(error = %e, "Cannot forget team channel");
+        }
+        return;
+    };
+
+    let action = format!("Deleted empty channel {}", channel.name);
+    let outcome = match discord.delete_channel(channel_id).await {
+        Ok(()) => {
+            info!(channel = %channel_id, "Deleted empty team channel");
+            if let Err(e) = db.remove_team_channel(channel_id) {
+                error!(error = %e, "Cannot forget team channel");
+            }
+            Outcome::Success(String::new())
+        }
+        Err(e) => {
+            error!(channel = %channel_id, error = %e, "Cannot delete empty team channel");
+            Outcome::Failure(e)
+        }
+    };
+    audit::log(
+        discord,
+        db,
+        guild_id,
+        vec![audit::entry(user_id, &action, outcome)],
+    )
+    .await;
+}
+
+/// Deletes the team channels that are still empty after `grace`, so those emptied while the bot
+/// was offline are not left behind.
+pub async fn sweep(discord: &dyn DiscordOps, db: &Database, user_id: UserId, grace: Duration) {
+    let channels = match db.team_channels() {
+        Ok(channels) => channels,
+        Err(e) => {
+            error!(error = %e, "Cannot list team channels");
+            return;
+        }
+    };
+    tokio::time::sleep(grace).await;
+    for (guild_id, channel_id) in channels {
+        delete_if_empty(discord, db, user_id, guild_id, channel_id).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::GuildSettings;
+    use crate::discord::fake::FakeDiscord;
+
+    const TEAM: u64 = 12;
+
+    fn guild() -> (FakeDiscord, Database) {
+        let discord = FakeDiscord::default();
+        discord.add_channel(TEAM, "team 1", Some(10));
+        let db = Database::open(":memory:").unwrap();
+        db.add_team_channel(GuildId::new(1), ChannelId::new(TEAM))
+            .unwrap();
+        (discord, db)
+    }
+
+    async fn cleanup(discord: &FakeDiscord, db: &Database, pending: &PendingCleanups) {
+        delete_when_empty(
+            discord,
+            db,
+            pending,
+            UserId::new(99),
+            GuildId::new(1),
+            ChannelId::new(TEAM),
+            Duration::ZERO,
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn deletes_empty_team_channels() {
+        let (discord, db) = guild();
+
+        cleanup(&discord, &db, &PendingCleanups::default()).await;
+
+        assert_eq!(
+            discord.state.lock().unwrap().deleted_channels,
+            vec![ChannelId::new(TEAM)]
+        );
+        assert!(!db.is_team_channel(ChannelId::new(TEAM)).unwrap());
+    }
+
+    #[tokio::test]
+    async fn keeps_channels_with_members() {
+        let (discord, db) = guild();
+        discord.connect(1, "member 1", TEAM);
+
+        cleanup(&discord, &db, &PendingCleanups::default()).await;
+
+        assert!(discord.state.lock().unwrap().deleted_channels.is_empty());
+        assert!(db.is_team_channel(ChannelId::new(TEAM)).unwrap());
+    }
+
+    #[tokio::test]
+    async fn logs_the_channel_name() {
+        let (discord, db) = guild();
+        discord.add_channel(31, "audit", None);
+        db.save_guild_settings(&GuildSettings {
+            audit_channel_id: Some(ChannelId::new(31)),
+            ..GuildSettings::new(GuildId::new(1))
+        })
+        .unwrap();
+
+        cleanup(&discord, &db, &PendingCleanups::default()).await;
+
+        let audit = discord.messages_in(ChannelId::new(31)).pop().unwrap();
+        assert_eq!(audit["embeds"][0]["title"], "Deleted empty channel team 1");
+    }
+
+    #[tokio::test]
+    async fn forgets_channels_deleted_by_hand() {
+        let (discord, db) = guild();
+        discord.state.lock().unwrap().channels.clear();
+
+        cleanup(&discord, &db, &PendingCleanups::default()).await;
+
+        assert!(discord.state.lock().unwrap().deleted_channels.is_empty());
+        assert!(!db.is_team_channel(ChannelId::new(TEAM)).unwrap());
+    }
+
+    #[tokio::test]
+    async fn sweeps_team_channels_left_empty() {
+        let (discord, db) = guild();
+
+        sweep(&discord, &db, UserId::new(99), Duration::ZERO).await;
+
+        assert_eq!(
+            discord.state.lock().unwrap().deleted_channels,
+            vec![ChannelId::new(TEAM)]
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_channels_it_did_not_create() {
+        let (discord, _) = guild();
+        let db = Database::open(":memory:").unwrap();
+
+        cleanup(&discord, &db, &PendingCleanups::default()).await;
+
+        assert!(discord.state.lock().unwrap().deleted_channels.is_empty());
+    }
+
+    #[test]
+    fn later_cleanups_replace_earlier_ones() {
+        let pending = PendingCleanups::default();
+        let channel_id = ChannelId::new(TEAM);
+
+        let first = pending.schedule(channel_id);
+        let second = pending.schedule(channel_id);
+
+        assert!(!pending.take(channel_id, first));
+        assert!(pending.take(channel_id, second));
+        assert!(!pending.take(channel_id, second));
+    }
+}
@@ -0,0 +1,149 @@
+use crate::db::MediaKind;
+
+pub type Rgb = (u8, u8, u8);
+
+const NAMED_COLORS: &[(&str, Rgb)] = &[
+    ("black", (0, 0, 0)),
+    ("white", (255, 255, 255)),
+    ("gray", (128, 128, 128)),
+    ("grey", (128, 128, 128)),
+    ("silver", (192, 192, 192)),
+    ("red", (255, 0, 0)),
+    ("maroon", (128, 0, 0)),
+    ("orange", (255, 165, 0)),
+    ("gold", (255, 215, 0)),
+    ("yellow", (255, 255, 0)),
+    ("lime", (0, 255, 0)),
+    ("green", (0, 128, 0)),
+    ("teal", (0, 128, 128)),
+    ("cyan", (0, 255, 255)),
+    ("aqua", (0, 255, 255)),
+    ("blue", (0, 0, 255)),
+    ("navy", (0, 0, 128)),
+    ("purple", (128, 0, 128)),
+    ("magenta", (255, 0, 255)),
+    ("fuchsia", (255, 0, 255)),
+    ("pink", (255, 192, 203)),
+    ("brown", (165, 42, 42)),
+    ("blurple", (88, 101, 242)),
+];
+
+/// Parses `#rgb`, `#rrggbb`, the same without `#`, or a named colour. Returns `None` for anything
+/// else instead of panicking.
+pub fn parse_color(s: &str) -> Option<Rgb> {
+    let s = s.trim();
+    let hex = s.strip_prefix('#').unwrap_or(s);
+
+    if hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        let digits: Vec<u8> = hex.chars().map(|c| c.to_digit(16).unwrap() as u8).collect();
+        match digits[..] {
+            [r, g, b] => return Some((r * 17, g * 17, b * 17)),
+            [r1, r2, g1, g2, b1, b2] => return Some((r1 * 16 + r2, g1 * 16 + g2, b1 * 16 + b2)),
+            _ => {}
+        }
+    }
+
+    NAMED_COLORS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        .map(|(_, color)| *color)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Provider {
+    Tmdb,
+    AniList,
+}
+
+/// Default embed colours per media kind for a single provider.
+#[derive(Clone, Copy, Debug)]
+pub struct ProviderTheme {
+    pub movie: Rgb,
+    pub show: Rgb,
+    pub anime: Rgb,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    pub tmdb: ProviderTheme,
+    pub anilist: ProviderTheme,
+    pub digest: Rgb,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        let tmdb_blue = (13, 37, 63);
+        let anilist_blue = (2, 169, 255);
+        Theme {
+            tmdb: ProviderTheme {
+                movie: tmdb_blue,
+                show: tmdb_blue,
+                anime: tmdb_blue,
+            },
+            anilist: ProviderTheme {
+                movie: anilist_blue,
+                show: anilist_blue,
+                anime: anilist_blue,
+            },
+            digest: tmdb_blue,
+        }
+    }
+}
+
+impl Theme {
+    pub fn color(&self, provider: Provider, kind: MediaKind) -> Rgb {
+        let provider_theme = match provider {
+            Provider::Tmdb => &self.tmdb,
+            Provider::AniList => &self.anilist,
+        };
+        match kind {
+            MediaKind::Movie => provider_theme.movie,
+            MediaKind::Show => provider_theme.show,
+            MediaKind::Anime => provider_theme.anime,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_hex_colours() {
+        assert_eq!(parse_color("#f80"), Some((255, 136, 0)));
+        assert_eq!(parse_color("#0d253f"), Some((13, 37, 63)));
+        assert_eq!(parse_color("0D253F"), Some((13, 37, 63)));
+        assert_eq!(parse_color("  #FFF "), Some((255, 255, 255)));
+    }
+
+    #[test]
+    fn parses_named_colours() {
+        assert_eq!(parse_color("blurple"), Some((88, 101, 242)));
+        assert_eq!(parse_color("Grey"), Some((128, 128, 128)));
+    }
+
+    #[test]
+    fn refuses_anything_else() {
+        assert_eq!(parse_color(""), None);
+        assert_eq!(parse_color("#"), None);
+        assert_eq!(parse_color("#ff"), None);
+        assert_eq!(parse_color("#ffff"), None);
+        assert_eq!(parse_color("#gggggg"), None);
+        assert_eq!(parse_color("#ff00zz"), None);
+        assert_eq!(parse_color("rainbow"), None);
+        assert_eq!(parse_color("#ffé"), None);
+    }
+
+    #[test]
+    fn theme_picks_the_colour_of_the_provider_and_kind() {
+        let mut theme = Theme::default();
+        theme.anilist.anime = (1, 2, 3);
+
+        assert_eq!(theme.color(Provider::AniList, MediaKind::Anime), (1, 2, 3));
+        assert_eq!(
+            theme.color(Provider::AniList, MediaKind::Movie),
+            (2, 169, 255)
+        );
+        assert_eq!(theme.color(Provider::Tmdb, MediaKind::Anime), (13, 37, 63));
+    }
+}
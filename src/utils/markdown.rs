@@ -0,0 +1,224 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+/// Maximum length of an embed title.
+pub const TITLE_LIMIT: usize = 256;
+/// Maximum length of an embed description.
+pub const DESCRIPTION_LIMIT: usize = 4096;
+/// Maximum length of an embed field name.
+pub const FIELD_NAME_LIMIT: usize = 256;
+/// Maximum length of an embed field value.
+pub const FIELD_VALUE_LIMIT: usize = 1024;
+
+static REGEX_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<\s*(/?)\s*([a-z0-9]+)[^>]*>").unwrap());
+static REGEX_SOURCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)(\s*(\(\s*source:[^)]*\)|\[\s*(source|written by)[^\]]*\]))+\s*$").unwrap()
+});
+/// A complete markdown link at the start of the text
+static REGEX_LINK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\[[^\]]*\]\([^)]*\)").unwrap());
+/// Links and the formatting markers outside of them
+static REGEX_TOKEN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[[^\]]*\]\([^)]*\)|\*\*|__|~~|\*|_").unwrap());
+static REGEX_BLANK_LINES: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\n{3,}").unwrap());
+
+/// Converts the HTML used in AniList descriptions into Discord markdown and decodes entities.
+/// Tags without a markdown counterpart are dropped.
+pub fn html_to_markdown(html: &str) -> String {
+    let markdown = REGEX_TAG.replace_all(html, |captures: &regex::Captures| {
+        let closing = !captures[1].is_empty();
+        match (captures[2].to_lowercase().as_str(), closing) {
+            ("br", _) => "\n",
+            ("p", false) => "",
+            ("p", true) => "\n\n",
+            ("i" | "em", _) => "*",
+            ("b" | "strong", _) => "**",
+            ("u", _) => "__",
+            ("s" | "strike" | "del", _) => "~~",
+            _ => "",
+        }
+    });
+
+    html_escape::decode_html_entities(&markdown).into_owned()
+}
+
+/// Removes trailers like "(Source: Crunchyroll)" or "[Written by MAL Rewrite]" from the end of a
+/// synopsis.
+pub fn strip_source(text: &str) -> String {
+    REGEX_SOURCE.replace(text, "").trim().to_string()
+}
+
+/// Shortens `text` to at most `limit` characters, cutting at the end of a sentence when possible
+/// and appending a "Read more" link. Links cut in half are dropped and formatting left open is
+/// closed, so the markdown before the link stays intact.
+pub fn truncate(text: &str, limit: usize, read_more_url: Option<&str>) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+
+    let suffix = match read_more_url {
+        Some(url) => format!("… [Read more]({url})"),
+        None => "…".to_string(),
+    };
+    let budget = limit.saturating_sub(suffix.chars().count());
+    let mut room = budget;
+    loop {
+        let kept = cut(text, room);
+        let closed = format!("{kept}{}", closing_markers(kept));
+        let length = closed.chars().count();
+        // Closing markers take room as well, make the cut shorter until they fit
+        if length <= budget || room == 0 {
+            return format!("{closed}{suffix}");
+        }
+        room = room.saturating_sub(length - budget);
+    }
+}
+
+/// The start of `text` up to at most `limit` characters, ending at a sentence or word when
+/// possible and never inside a link or formatting marker.
+fn cut(text: &str, limit: usize) -> &str {
+    let cut = text
+        .char_indices()
+        .nth(limit)
+        .map(|(i, _)| i)
+        .unwrap_or(text.len());
+    let head = &text[..cut];
+
+    // Prefer a sentence boundary, unless that throws away more than half of the text
+    let sentence_end = head
+        .rmatch_indices(['.', '!', '?', '\n'])
+        .map(|(i, _)| i + 1)
+        .find(|&i| i >= cut / 2);
+    let kept = match sentence_end {
+        Some(end) => &head[..end],
+        None => match head.rfind(char::is_whitespace) {
+            Some(end) if end >= cut / 2 => &head[..end],
+            _ => head,
+        },
+    };
+
+    let mut end = kept.len();
+    let mut i = 0;
+    while let Some(offset) = kept[i..].find('[') {
+        let start = i + offset;
+        match REGEX_LINK.find(&kept[start..]) {
+            Some(link) => i = start + link.end(),
+            None => {
+                end = start;
+                break;
+            }
+        }
+    }
+    kept[..end]
+        .trim_end()
+        .trim_end_matches(['*', '_', '~'])
+        .trim_end()
+}
+
+/// The markers that close the formatting still open at the end of `text`, innermost first.
+fn closing_markers(text: &str) -> String {
+    let mut open: Vec<&str> = Vec::new();
+    for token in REGEX_TOKEN.find_iter(text) {
+        let marker = token.as_str();
+        if marker.starts_with('[') {
+            continue;
+        }
+        match open.iter().rposition(|&m| m == marker) {
+            Some(i) => {
+                open.remove(i);
+            }
+            None => open.push(marker),
+        }
+    }
+    open.iter().rev().copied().collect()
+}
+
+/// Cleans up a synopsis from any provider so it fits in an embed description.
+pub fn sanitize(text: &str, read_more_url: Option<&str>, limit: usize) -> String {
+    let markdown = html_to_markdown(text);
+    let stripped = strip_source(&markdown);
+    let collapsed = REGEX_BLANK_LINES.replace_all(&stripped, "\n\n");
+    truncate(collapsed.trim(), limit, read_more_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_html_to_markdown() {
+        let html = "<p>A <b>bold</b> and <i>quiet</I> hero.<br>Tom &amp; Jerry&#039;s <u>rival</u>\
+                    <span class=\"x\">!</span></p>";
+
+        assert_eq!(
+            html_to_markdown(html),
+            "A **bold** and *quiet* hero.\nTom & Jerry's __rival__!\n\n"
+        );
+    }
+
+    #[test]
+    fn strips_source_lines() {
+        assert_eq!(
+            strip_source("A story.\n\n(Source: Crunchyroll)"),
+            "A story."
+        );
+        assert_eq!(
+            strip_source("A story. [Written by MAL Rewrite]"),
+            "A story."
+        );
+        assert_eq!(
+            strip_source("A (source: hidden) story."),
+            "A (source: hidden) story."
+        );
+    }
+
+    #[test]
+    fn keeps_short_text() {
+        assert_eq!(truncate("Short.", 6, Some("https://x")), "Short.");
+    }
+
+    #[test]
+    fn truncates_multibyte_text_at_the_limit() {
+        let text = "日本語の文章です。".repeat(20);
+
+        let truncated = truncate(&text, 50, None);
+
+        assert!(truncated.chars().count() <= 50);
+        assert!(truncated.ends_with("…"));
+        assert!(text.starts_with(truncated.trim_end_matches('…')));
+    }
+
+    #[test]
+    fn truncates_at_a_sentence_and_links_to_the_rest() {
+        let text = "First sentence. Second sentence that goes on and on.";
+
+        assert_eq!(
+            truncate(text, 40, Some("https://x")),
+            "First sentence.… [Read more](https://x)"
+        );
+    }
+
+    #[test]
+    fn closes_formatting_cut_in_half() {
+        let text = "Some **very bold words that go on** and on";
+
+        let truncated = truncate(text, 25, None);
+
+        assert_eq!(truncated, "Some **very bold words**…");
+        assert!(truncated.chars().count() <= 25);
+    }
+
+    #[test]
+    fn does_not_split_markers() {
+        // The cut lands between the two asterisks of the bold marker
+        assert_eq!(truncate("Unbroken**bold**", 10, None), "Unbroken…");
+    }
+
+    #[test]
+    fn drops_links_cut_in_half() {
+        let text = "See [the website](https://example.com/page) for everything else";
+
+        assert_eq!(truncate(text, 30, None), "See…");
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use image::imageops::FilterType;
+
+use crate::utils::color::Rgb;
+
+/// Posters that take longer than this to download are announced with the theme colour instead,
+/// the interaction has to be answered within 3 seconds.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_millis(1500);
+/// Number of poster colours remembered, the oldest are forgotten first.
+const CACHE_SIZE: usize = 512;
+
+/// Returns the most vibrant dominant colour of an encoded image, or `None` if the bytes cannot be
+/// decoded.
+///
+/// Pixels are grouped into coarse colour buckets and every bucket is scored by its pixel count
+/// weighted by saturation, so a small bright accent can win over a large grey background. Nearly
+/// black and nearly white pixels are ignored unless the whole image consists of them.
+pub fn dominant_color(bytes: &[u8]) -> Option<Rgb> {
+    let image = image::load_from_memory(bytes).ok()?;
+    let thumbnail = image.resize(64, 64, FilterType::Triangle).to_rgb8();
+
+    // Sum of r, g, b and the pixel count per bucket of 4 bits per channel
+    let mut buckets: HashMap<u16, [u64; 4]> = HashMap::new();
+    let mut fallback = [0u64; 4];
+
+    for pixel in thumbnail.pixels() {
+        let [r, g, b] = pixel.0;
+        fallback[0] += r as u64;
+        fallback[1] += g as u64;
+        fallback[2] += b as u64;
+        fallback[3] += 1;
+
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        if max < 32 || min > 224 {
+            continue;
+        }
+
+        let key = ((r as u16 >> 4) << 8) | ((g as u16 >> 4) << 4) | (b as u16 >> 4);
+        let bucket = buckets.entry(key).or_insert([0; 4]);
+        bucket[0] += r as u64;
+        bucket[1] += g as u64;
+        bucket[2] += b as u64;
+        bucket[3] += 1;
+    }
+
+    let best = buckets
+        .values()
+        .map(|bucket| {
+            let color = average(bucket);
+            (bucket[3] as f32 * (0.1 + saturation(color)), color)
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, color)| color);
+
+    match best {
+        Some(color) => Some(color),
+        None if fallback[3] > 0 => Some(average(&fallback)),
+        None => None,
+    }
+}
+
+fn average(sums: &[u64; 4]) -> Rgb {
+    (
+        (sums[0] / sums[3]) as u8,
+        (sums[1] / sums[3]) as u8,
+        (sums[2] / sums[3]) as u8,
+    )
+}
+
+fn saturation((r, g, b): Rgb) -> f32 {
+    let max = r.max(g).max(b) as f32;
+    let min = r.min(g).min(b) as f32;
+    if max == 0.0 {
+        0.0
+    } else {
+        (max - min) / max
+    }
+}
+
+/// Remembers the colour extracted for recent poster URLs so each image is only downloaded once.
+pub struct PosterColors {
+    http: reqwest::Client,
+    cache: Mutex<Cache>,
+}
+
+impl Default for PosterColors {
+    fn default() -> PosterColors {
+        PosterColors {
+            http: reqwest::Client::builder()
+                .timeout(DOWNLOAD_TIMEOUT)
+                .build()
+                .expect("Cannot create HTTP client"),
+            cache: Mutex::new(Cache::new(CACHE_SIZE)),
+        }
+    }
+}
+
+impl PosterColors {
+    pub async fn get(&self, url: &str) -> Option<Rgb> {
+        if let Some(color) = self.cache.lock().unwrap().colors.get(url) {
+            return Some(*color);
+        }
+
+        let bytes = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .ok()?
+            .bytes()
+            .await
+            .ok()?;
+        // Decoding takes a while for large posters, keep it off the async runtime
+        let color = tokio::task::spawn_blocking(move || dominant_color(&bytes))
+            .await
+            .ok()??;
+
+        self.cache.lock().unwrap().insert(url.to_string(), color);
+        Some(color)
+    }
+}
+
+struct Cache {
+    capacity: usize,
+    colors: HashMap<String, Rgb>,
+    /// URLs from oldest to newest
+    order: VecDeque<String>,
+}
+
+impl Cache {
+    fn new(capacity: usize) -> Cache {
+        Cache {
+            capacity,
+            colors: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn insert(&mut self, url: String, color: Rgb) {
+        if self.colors.insert(url.clone(), color).is_some() {
+            return;
+        }
+        self.order.push_back(url);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.colors.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, Rgb as Pixel, RgbImage};
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    fn png(image: &RgbImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn solid_colour() {
+        let image = RgbImage::from_pixel(16, 16, Pixel([200, 40, 40]));
+
+        assert_eq!(dominant_color(&png(&image)), Some((200, 40, 40)));
+    }
+
+    #[test]
+    fn colour_covering_most_of_the_image() {
+        // The top three quarters red, the bottom quarter blue
+        let image = RgbImage::from_fn(16, 16, |_, y| {
+            if y < 12 {
+                Pixel([200, 40, 40])
+            } else {
+                Pixel([40, 40, 200])
+            }
+        });
+
+        assert_eq!(dominant_color(&png(&image)), Some((200, 40, 40)));
+    }
+
+    #[test]
+    fn black_images_fall_back_to_the_average() {
+        let image = RgbImage::from_pixel(16, 16, Pixel([0, 0, 0]));
+
+        assert_eq!(dominant_color(&png(&image)), Some((0, 0, 0)));
+    }
+
+    #[test]
+    fn undecodable_bytes() {
+        assert_eq!(dominant_color(b"not an image"), None);
+        assert_eq!(dominant_color(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn downloads_each_poster_once() {
+        let server = MockServer::start().await;
+        let image = RgbImage::from_pixel(16, 16, Pixel([200, 40, 40]));
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(png(&image)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let colors = PosterColors::default();
+        let url = format!("{}/poster.png", server.uri());
+
+        assert_eq!(colors.get(&url).await, Some((200, 40, 40)));
+        assert_eq!(colors.get(&url).await, Some((200, 40, 40)));
+    }
+
+    #[tokio::test]
+    async fn slow_posters_time_out() {
+        let server = MockServer::start().await;
+        let image = RgbImage::from_pixel(16, 16, Pixel([200, 40, 40]));
+        Mock::given(method("GET"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(png(&image))
+                    .set_delay(DOWNLOAD_TIMEOUT * 2),
+            )
+            .mount(&server)
+            .await;
+
+        let color = PosterColors::default()
+            .get(&format!("{}/poster.png", server.uri()))
+            .await;
+
+        assert_eq!(color, None);
+    }
+
+    #[test]
+    fn forgets_the_oldest_colours() {
+        let mut cache = Cache::new(2);
+
+        cache.insert("a".to_string(), (1, 1, 1));
+        cache.insert("b".to_string(), (2, 2, 2));
+        cache.insert("a".to_string(), (1, 1, 1));
+        cache.insert("c".to_string(), (3, 3, 3));
+
+        assert!(!cache.colors.contains_key("a"));
+        assert_eq!(cache.colors.len(), 2);
+        assert_eq!(cache.order, ["b", "c"]);
+    }
+}